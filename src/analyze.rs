@@ -0,0 +1,165 @@
+use crate::{calculate_layout, calculate_row_count, cropped_dimensions, ImageData, Settings};
+
+// How much pixel area a few packing strategies would need for the frames, read
+// from the image headers only. The grid sizes every cell to the largest
+// frame, as --uniform-max does, so it holds all of them like the others.
+pub fn print_comparison(images_data: &[ImageData], settings: &Settings, skipped: &mut Vec<String>) {
+    let mut sizes = Vec::new();
+    for image_info in images_data
+        .iter()
+        .filter(|image_info| !image_info.page_break)
+    {
+        if image_info.placeholder {
+            continue;
+        }
+        match image_info.dimensions() {
+            Ok(dimensions) => sizes.push(cropped_dimensions(dimensions, settings)),
+            Err(_) => skipped.push(format!("{} (unreadable)", image_info.name)),
+        }
+    }
+    sizes.retain(|&(width, height)| width > 0 && height > 0);
+    if sizes.is_empty() {
+        println!("No frames to analyze");
+        return;
+    }
+
+    let results = [
+        ("grid (auto rows)", grid_size(&sizes)),
+        ("shelf", best_width(&sizes, shelf_height)),
+        ("maxrects", best_width(&sizes, maxrects_height)),
+    ];
+    println!("Analyzed {} frames:", sizes.len());
+    for (name, (width, height)) in results.iter() {
+        println!(
+            "  {:<16} {:>5}x{:<5} {:>10} px",
+            name,
+            width,
+            height,
+            *width as u64 * *height as u64
+        );
+    }
+    let area = |size: &(u32, u32)| size.0 as u64 * size.1 as u64;
+    let (best, best_size) = results.iter().min_by_key(|(_, size)| area(size)).unwrap();
+    let grid_area = area(&results[0].1);
+    println!(
+        "Smallest: {} ({:.1}% of the grid area)",
+        best,
+        area(best_size) as f64 * 100.0 / grid_area as f64
+    );
+}
+
+fn grid_size(sizes: &[(u32, u32)]) -> (u32, u32) {
+    let cell = sizes.iter().fold((0, 0), |cell, &(width, height)| {
+        (cell.0.max(width), cell.1.max(height))
+    });
+    let columns = calculate_row_count(sizes.len()).max(1);
+    let layout = calculate_layout(columns, sizes.len(), cell, false);
+    (layout.width, layout.height)
+}
+
+// Tries a few sheet widths around the square root of the total area and keeps
+// the one giving the smallest sheet.
+fn best_width(sizes: &[(u32, u32)], height_for: fn(&[(u32, u32)], u32) -> u32) -> (u32, u32) {
+    let widest = sizes.iter().map(|size| size.0).max().unwrap_or(0);
+    let total: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    let side = (total as f64).sqrt();
+    [1.0, 1.2, 1.5, 2.0]
+        .iter()
+        .map(|factor| widest.max((side * factor).ceil() as u32))
+        .map(|width| (width, height_for(sizes, width)))
+        .min_by_key(|&(width, height)| (width as u64 * height as u64, width.abs_diff(height)))
+        .unwrap()
+}
+
+// Next-fit shelves: frames sorted by decreasing height fill rows left to
+// right, and a new shelf starts when the next one doesn't fit.
+fn shelf_height(sizes: &[(u32, u32)], sheet_width: u32) -> u32 {
+    let mut sorted = sizes.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+    let (mut x, mut top, mut shelf) = (0, 0, 0);
+    for (width, height) in sorted {
+        if x + width > sheet_width {
+            top += shelf;
+            x = 0;
+            shelf = 0;
+        }
+        x += width;
+        shelf = shelf.max(height);
+    }
+    top + shelf
+}
+
+// MaxRects on a sheet of the given width that is tall enough for any
+// placement. Frames go as low as possible, ties broken by the best short side
+// fit.
+fn maxrects_height(sizes: &[(u32, u32)], sheet_width: u32) -> u32 {
+    let mut sorted = sizes.to_vec();
+    sorted.sort_by_key(|&(width, height)| std::cmp::Reverse((width.max(height), width * height)));
+    let sheet_height = sizes.iter().map(|size| size.1).sum();
+    let mut free = vec![(0, 0, sheet_width, sheet_height)];
+    let mut used_height = 0;
+    for (width, height) in sorted {
+        let Some(&(x, y, _, _)) = free
+            .iter()
+            .filter(|rect| rect.2 >= width && rect.3 >= height)
+            .min_by_key(|rect| {
+                let (short, long) = (
+                    (rect.2 - width).min(rect.3 - height),
+                    (rect.2 - width).max(rect.3 - height),
+                );
+                (rect.1 + height, short, long)
+            })
+        else {
+            return sheet_height;
+        };
+        used_height = used_height.max(y + height);
+        let placed = (x, y, width, height);
+        free = free
+            .into_iter()
+            .flat_map(|rect| split_free_rect(rect, placed))
+            .collect();
+        // Drop free rectangles inside another one, keeping one of equal pairs.
+        let all = free.clone();
+        let mut index = 0;
+        free.retain(|rect| {
+            index += 1;
+            !all.iter().enumerate().any(|(other_index, other)| {
+                contains(*other, *rect) && (other != rect || other_index < index - 1)
+            })
+        });
+    }
+    used_height
+}
+
+// The parts of a free rectangle left uncovered by a placed one.
+fn split_free_rect(
+    rect: (u32, u32, u32, u32),
+    placed: (u32, u32, u32, u32),
+) -> Vec<(u32, u32, u32, u32)> {
+    let (x, y, width, height) = rect;
+    let (px, py, pwidth, pheight) = placed;
+    if px >= x + width || px + pwidth <= x || py >= y + height || py + pheight <= y {
+        return vec![rect];
+    }
+    let mut parts = Vec::new();
+    if px > x {
+        parts.push((x, y, px - x, height));
+    }
+    if px + pwidth < x + width {
+        parts.push((px + pwidth, y, x + width - px - pwidth, height));
+    }
+    if py > y {
+        parts.push((x, y, width, py - y));
+    }
+    if py + pheight < y + height {
+        parts.push((x, py + pheight, width, y + height - py - pheight));
+    }
+    parts
+}
+
+fn contains(outer: (u32, u32, u32, u32), inner: (u32, u32, u32, u32)) -> bool {
+    inner.0 >= outer.0
+        && inner.1 >= outer.1
+        && inner.0 + inner.2 <= outer.0 + outer.2
+        && inner.1 + inner.3 <= outer.1 + outer.3
+}
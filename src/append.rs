@@ -0,0 +1,169 @@
+use image::GenericImageView;
+
+use crate::{apply_origin, calculate_layout, json, Layout, Origin, Sprite, SpritesheetErr};
+
+// A frame already on the sheet and its rect.
+struct Cell {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+// The frames already on the sheet, read from the JSON atlas written next to
+// it.
+fn read_cells(atlas_path: &std::path::Path) -> Result<Vec<Cell>, SpritesheetErr> {
+    let error = |message: &str| {
+        SpritesheetErr::ImportError(format!("{}: {}", atlas_path.display(), message))
+    };
+    let contents = std::fs::read_to_string(atlas_path).map_err(|_| error("can't read atlas"))?;
+    let atlas = json::parse(&contents).ok_or_else(|| error("invalid json"))?;
+    let Some(json::JsonValue::Object(entries)) = atlas.get("frames") else {
+        return Err(error("no frames"));
+    };
+
+    let mut cells = Vec::new();
+    for (name, frame) in entries {
+        let rect = frame.get("frame");
+        let field = |key: &str| {
+            rect.and_then(|rect| rect.get(key))
+                .and_then(|value| value.as_u32())
+        };
+        let (Some(x), Some(y), Some(width), Some(height)) =
+            (field("x"), field("y"), field("w"), field("h"))
+        else {
+            return Err(error(&format!("frame {} has no valid rect", name)));
+        };
+        cells.push(Cell {
+            name: name.clone(),
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+    Ok(cells)
+}
+
+// Lays out the frames of an existing sheet plus the new images for --append.
+// Frames listed in the sheet's atlas keep their cells and pixels, images not
+// in it yet fill the free cells in row order, and rows are only added when
+// no free cell is left. Images whose name is already on the sheet are
+// skipped. Cells are counted from the --origin corner like a packed sheet's,
+// so with a bottom origin added rows go on top and move the old frames down.
+pub fn append_frames(
+    images: Vec<Sprite>,
+    sheet_path: &std::path::Path,
+    origin: Origin,
+) -> Result<(Layout, Vec<Sprite>), SpritesheetErr> {
+    let sheet = image::open(sheet_path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", sheet_path.display())))?;
+    let atlas_path = sheet_path.with_extension("json");
+    let cells = read_cells(&atlas_path)?;
+    // The atlas doesn't record the cell size, only the frames show it.
+    let Some(first) = cells.first() else {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "{} has no frames to take the cell size from",
+            atlas_path.display()
+        )));
+    };
+    let cell = (first.width, first.height);
+    let on_grid = |frame: &Cell| {
+        (frame.width, frame.height) == cell
+            && frame.x.is_multiple_of(cell.0)
+            && frame.y.is_multiple_of(cell.1)
+            && frame.x as u64 + cell.0 as u64 <= sheet.width() as u64
+            && frame.y as u64 + cell.1 as u64 <= sheet.height() as u64
+    };
+    if cell.0 == 0 || cell.1 == 0 || !cells.iter().all(on_grid) {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "{} isn't a grid of equal cells",
+            sheet_path.display()
+        )));
+    }
+    let new_images: Vec<Sprite> = images
+        .into_iter()
+        .filter(|sprite| cells.iter().all(|frame| frame.name != sprite.name))
+        .collect();
+    if let Some(sprite) = new_images
+        .iter()
+        .find(|sprite| !sprite.placeholder && sprite.image.dimensions() != cell)
+    {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "{} is {}x{}, the cells of {} are {}x{}",
+            sprite.name,
+            sprite.image.width(),
+            sprite.image.height(),
+            sheet_path.display(),
+            cell.0,
+            cell.1
+        )));
+    }
+
+    let columns = (sheet.width() / cell.0).max(1);
+    let sheet_rows = sheet.height() / cell.1;
+    // Positions are worked out counting from the origin corner, then
+    // flipped back into place by apply_origin, which is its own inverse.
+    let flip = |position: (u32, u32), rows: u32| {
+        let mut layout = Layout {
+            rows,
+            positions: vec![position],
+            ..calculate_layout(columns, 0, cell, false)
+        };
+        apply_origin(&mut layout, origin);
+        layout.positions[0]
+    };
+    let occupied: Vec<(u32, u32)> = cells
+        .iter()
+        .map(|frame| {
+            let (x, y) = flip((frame.x, frame.y), sheet_rows);
+            (x / cell.0, y / cell.1)
+        })
+        .collect();
+    let mut free = (0..).map(|index| (index % columns, index / columns));
+    let mut positions = Vec::new();
+    let mut sprites = Vec::new();
+    for frame in cells.iter() {
+        positions.push(flip((frame.x, frame.y), sheet_rows));
+        sprites.push(Sprite {
+            name: frame.name.clone(),
+            image: sheet.crop_imm(frame.x, frame.y, frame.width, frame.height),
+            hash: None,
+            avg_color: None,
+            duration: None,
+            placeholder: false,
+            trim: None,
+            angle: 0.0,
+            mips: Vec::new(),
+            layers: Vec::new(),
+        });
+    }
+    let added = new_images.len();
+    for sprite in new_images {
+        let (column, row) = free.find(|cell| !occupied.contains(cell)).unwrap();
+        positions.push((column * cell.0, row * cell.1));
+        sprites.push(sprite);
+    }
+    let rows = positions
+        .iter()
+        .map(|position| position.1 / cell.1 + 1)
+        .max()
+        .unwrap_or(0)
+        .max(sheet_rows);
+    println!(
+        "Appending {} new frames to the {} on {}",
+        added,
+        cells.len(),
+        sheet_path.display()
+    );
+
+    let mut layout = Layout {
+        rows,
+        height: rows * cell.1,
+        positions,
+        ..calculate_layout(columns, 0, cell, false)
+    };
+    apply_origin(&mut layout, origin);
+    Ok((layout, sprites))
+}
@@ -0,0 +1,91 @@
+use crate::{get_image_format, json, sniff_format, ImageData, SpritesheetErr};
+
+pub struct FrameTag {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub direction: String,
+}
+
+pub struct AsepriteImport {
+    pub images: Vec<ImageData>,
+    pub frame_tags: Vec<FrameTag>,
+}
+
+pub fn read_frames(path: &std::path::Path) -> Result<AsepriteImport, SpritesheetErr> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't read {}", path.display())))?;
+    let root = json::parse(&contents).ok_or_else(|| {
+        SpritesheetErr::ImportError(format!("invalid json in {}", path.display()))
+    })?;
+    let base_dir = path.parent().unwrap_or(std::path::Path::new(""));
+
+    let mut frames: Vec<(&str, &json::JsonValue)> = Vec::new();
+    match root.get("frames") {
+        Some(json::JsonValue::Object(entries)) => {
+            for (name, frame) in entries {
+                frames.push((name, frame));
+            }
+        }
+        Some(json::JsonValue::Array(values)) => {
+            for frame in values {
+                let name = frame.get("filename").and_then(|value| value.as_str());
+                let name = name.ok_or_else(|| {
+                    SpritesheetErr::ImportError("frame without filename".to_string())
+                })?;
+                frames.push((name, frame));
+            }
+        }
+        _ => return Err(SpritesheetErr::ImportError("no frames list".to_string())),
+    }
+
+    let mut images = Vec::new();
+    for (name, frame) in frames {
+        let image_path = base_dir.join(name);
+        let format = image_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(get_image_format)
+            .or_else(|| sniff_format(&image_path))
+            .ok_or_else(|| SpritesheetErr::ImportError(format!("unsupported image {}", name)))?;
+        images.push(ImageData {
+            name: name.to_string(),
+            path: image_path,
+            format,
+            duration: frame.get("duration").and_then(|value| value.as_u32()),
+            bytes: None,
+            placeholder: false,
+            page_break: false,
+            layers: Vec::new(),
+        });
+    }
+
+    let mut frame_tags = Vec::new();
+    let tags = root
+        .get("meta")
+        .and_then(|meta| meta.get("frameTags"))
+        .and_then(|tags| tags.as_array())
+        .unwrap_or(&[]);
+    for tag in tags {
+        let name = tag.get("name").and_then(|value| value.as_str());
+        let from = tag.get("from").and_then(|value| value.as_u32());
+        let to = tag.get("to").and_then(|value| value.as_u32());
+        if let (Some(name), Some(from), Some(to)) = (name, from, to) {
+            frame_tags.push(FrameTag {
+                name: name.to_string(),
+                from,
+                to,
+                direction: tag
+                    .get("direction")
+                    .and_then(|value| value.as_str())
+                    .unwrap_or("forward")
+                    .to_string(),
+            });
+        }
+    }
+
+    if images.is_empty() {
+        return Err(SpritesheetErr::NoImagesFound);
+    }
+    Ok(AsepriteImport { images, frame_tags })
+}
@@ -0,0 +1,1102 @@
+use crate::{aseprite::FrameTag, Layout, Settings, SheetInfo, Sprite, SpritesheetErr};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AtlasFormat {
+    Json,
+    JsonArray,
+    Bin,
+    Spine,
+    Toml,
+    CHeader,
+    FlatBuffer,
+    HtmlPreview,
+    BmFont,
+    // The writer at this index in Settings::writers.
+    Custom(usize),
+}
+
+// A text atlas format registered with PackOptions::register_writer. Like the
+// built-in ones it's called with the layout and returns the file's contents;
+// the frame names the built-in writers capture are passed in a SheetInfo.
+#[derive(Clone)]
+pub struct Writer {
+    pub name: String,
+    pub write: std::sync::Arc<WriteFn>,
+}
+
+type WriteFn = dyn Fn(&Layout, &SheetInfo) -> String + Send + Sync;
+
+impl AtlasFormat {
+    pub const NAMES: &'static [&'static str] = &[
+        "json",
+        "json-array",
+        "bin",
+        "spine",
+        "toml",
+        "c-header",
+        "flatbuffer",
+        "html-preview",
+        "bmfont",
+    ];
+
+    pub fn parse(str: &str) -> Option<AtlasFormat> {
+        match str {
+            "json" => Some(AtlasFormat::Json),
+            "json-array" => Some(AtlasFormat::JsonArray),
+            "bin" => Some(AtlasFormat::Bin),
+            "spine" => Some(AtlasFormat::Spine),
+            "toml" => Some(AtlasFormat::Toml),
+            "c-header" => Some(AtlasFormat::CHeader),
+            "flatbuffer" => Some(AtlasFormat::FlatBuffer),
+            "html-preview" => Some(AtlasFormat::HtmlPreview),
+            "bmfont" => Some(AtlasFormat::BmFont),
+            _ => None,
+        }
+    }
+
+    fn extension<'a>(&self, settings: &'a Settings) -> &'a str {
+        match self {
+            AtlasFormat::Json | AtlasFormat::JsonArray => "json",
+            AtlasFormat::Bin => "bin",
+            AtlasFormat::Spine => "atlas",
+            AtlasFormat::Toml => "toml",
+            AtlasFormat::CHeader => "h",
+            AtlasFormat::FlatBuffer => "fb",
+            AtlasFormat::HtmlPreview => "html",
+            AtlasFormat::BmFont => "fnt",
+            AtlasFormat::Custom(index) => &settings.writers[*index].name,
+        }
+    }
+}
+
+pub fn save_atlas(
+    format: AtlasFormat,
+    layout: &Layout,
+    images: &[Sprite],
+    frame_tags: &[FrameTag],
+    image_path: &std::path::Path,
+    settings: &Settings,
+) -> Result<(), SpritesheetErr> {
+    let image_name = image_path.file_name().unwrap().to_string_lossy();
+    let contents = match format {
+        AtlasFormat::Bin => write_bin(layout)?,
+        AtlasFormat::FlatBuffer => write_flatbuffer(layout),
+        _ => {
+            let writers = text_writers(images, frame_tags, &image_name, settings);
+            let (_, write) = writers
+                .into_iter()
+                .find(|(writer_format, _)| *writer_format == format)
+                .unwrap();
+            write(layout).into_bytes()
+        }
+    };
+
+    let path_to_save = image_path.with_extension(format.extension(settings));
+
+    std::fs::write(path_to_save, contents).map_err(|_| SpritesheetErr::AtlasSaveError)
+}
+
+type TextWriter<'a> = Box<dyn Fn(&Layout) -> String + 'a>;
+
+// The built-in text formats as writers of the same shape as registered ones,
+// the frames and settings they need captured, followed by the registered
+// writers. Binary formats (bin, flatbuffer) are written separately.
+fn text_writers<'a>(
+    images: &'a [Sprite],
+    frame_tags: &'a [FrameTag],
+    image_name: &'a str,
+    settings: &'a Settings,
+) -> Vec<(AtlasFormat, TextWriter<'a>)> {
+    let mut writers: Vec<(AtlasFormat, TextWriter<'a>)> = vec![
+        (
+            AtlasFormat::Json,
+            Box::new(|layout| write_json(layout, images, frame_tags, image_name, false, settings)),
+        ),
+        (
+            AtlasFormat::JsonArray,
+            Box::new(|layout| write_json(layout, images, frame_tags, image_name, true, settings)),
+        ),
+        (
+            AtlasFormat::Spine,
+            Box::new(|layout| write_spine(layout, images, image_name, settings)),
+        ),
+        (
+            AtlasFormat::Toml,
+            Box::new(|layout| write_toml(layout, images, frame_tags, image_name, settings)),
+        ),
+        (
+            AtlasFormat::CHeader,
+            Box::new(|layout| write_c_header(layout, image_name, settings)),
+        ),
+        (
+            AtlasFormat::HtmlPreview,
+            Box::new(|layout| write_html(layout, images, image_name, settings)),
+        ),
+        (
+            AtlasFormat::BmFont,
+            Box::new(|layout| write_bmfont(layout, images, image_name, settings)),
+        ),
+    ];
+    for (index, writer) in settings.writers.iter().enumerate() {
+        let write = writer.write.clone();
+        writers.push((
+            AtlasFormat::Custom(index),
+            Box::new(move |layout| {
+                let frame_names = images
+                    .iter()
+                    .take(layout.positions.len())
+                    .enumerate()
+                    .map(|(index, sprite)| frame_key(&sprite.name, index, settings))
+                    .collect();
+                let sheet = SheetInfo {
+                    image_name: sheet_name(image_name, settings),
+                    frame_names,
+                };
+                write(layout, &sheet)
+            }),
+        ));
+    }
+    writers
+}
+
+// The frame indices in the order their entries are written: input order, or
+// with --atlas-order position by cell, top to bottom and then left to right.
+// Frame indices (animations, tags, {index}) keep meaning the input order.
+fn entry_order(layout: &Layout, images: &[Sprite], settings: &Settings) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..images.len().min(layout.positions.len())).collect();
+    if settings.atlas_by_position {
+        order.sort_by_key(|&index| (layout.positions[index].1, layout.positions[index].0));
+    }
+    order
+}
+
+pub fn write_json(
+    layout: &Layout,
+    images: &[Sprite],
+    frame_tags: &[FrameTag],
+    image_name: &str,
+    array: bool,
+    settings: &Settings,
+) -> String {
+    let mut frames = Vec::new();
+    for index in entry_order(layout, images, settings) {
+        let (sprite, position) = (&images[index], &layout.positions[index]);
+        let key = json_string(&frame_key(&sprite.name, index, settings));
+        // The TexturePacker "JSON (Array)" variant keeps frame order and
+        // moves the key into a `filename` field.
+        let opening = match array {
+            true => format!("    {{\n      \"filename\": {},\n", key),
+            false => format!("    {}: {{\n", key),
+        };
+        let (width, height) = layout.frame_size(index);
+        let mut frame = match sprite.trim {
+            // Trimmed frames use the TexturePacker fields, so the original
+            // frame bounds can be rebuilt from the packed rect.
+            Some(trim) => {
+                let (x, y) = crate::align_in_cell(layout, &sprite.image, index, settings);
+                let [x, y, w, h] = rect_coords(
+                    layout,
+                    (x, y, sprite.image.width(), sprite.image.height()),
+                    settings,
+                );
+                // Nothing is ever rotated to pack; --no-rotation leaves the
+                // flag out for loaders that don't know it.
+                let rotated = match settings.no_rotation {
+                    true => "",
+                    false => "\n      \"rotated\": false,",
+                };
+                format!(
+                    "{}      \"frame\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}},{}\n      \"trimmed\": true,\n      \"spriteSourceSize\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}},\n      \"sourceSize\": {{\"w\": {}, \"h\": {}}}",
+                    opening,
+                    x,
+                    y,
+                    w,
+                    h,
+                    rotated,
+                    trim.x,
+                    trim.y,
+                    sprite.image.width(),
+                    sprite.image.height(),
+                    trim.source_width,
+                    trim.source_height
+                )
+            }
+            // With --mips the cell holds the whole chain, the frame is only
+            // its full-size level.
+            None if !sprite.mips.is_empty() => {
+                let (x, y) = crate::align_in_cell(layout, &sprite.image, index, settings);
+                let level = sprite.mips[0];
+                let [x, y, w, h] = rect_coords(layout, (x, y, level.2, level.3), settings);
+                format!(
+                    "{}      \"frame\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}",
+                    opening, x, y, w, h
+                )
+            }
+            None => {
+                let [x, y, w, h] =
+                    rect_coords(layout, (position.0, position.1, width, height), settings);
+                format!(
+                    "{}      \"frame\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}",
+                    opening, x, y, w, h
+                )
+            }
+        };
+        if let Some(color) = sprite.avg_color {
+            frame += &format!(
+                ",\n      \"avgColor\": \"#{:02X}{:02X}{:02X}\"",
+                color[0], color[1], color[2]
+            );
+        }
+        if let Some(duration) = sprite.duration {
+            frame += &format!(",\n      \"duration\": {}", duration);
+        }
+        if let Some(hash) = sprite.hash {
+            frame += &format!(",\n      \"hash\": \"{}\"", short_hash(hash));
+        }
+        if sprite.angle != 0.0 {
+            frame += &format!(",\n      \"angle\": {}", sprite.angle);
+        }
+        if let Some(([x, y], angle)) = ring_placement(layout, index, settings) {
+            frame += &format!(
+                ",\n      \"ring\": {{\"center\": {{\"x\": {}, \"y\": {}}}, \"angle\": {}}}",
+                x, y, angle
+            );
+        }
+        if settings.compute_bounds && !sprite.placeholder {
+            let (x, y, w, h) = bounds_in_cell(layout, sprite, index, settings);
+            frame += &format!(
+                ",\n      \"opaqueBounds\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}",
+                x, y, w, h
+            );
+        }
+        if sprite.mips.len() > 1 {
+            let levels: Vec<String> = mip_rects(layout, sprite, index, settings)
+                .iter()
+                .map(|[x, y, w, h]| {
+                    format!("{{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}", x, y, w, h)
+                })
+                .collect();
+            frame += &format!(",\n      \"mips\": [{}]", levels.join(", "));
+        }
+        // Like TexturePacker, the pivot is normalized to the untrimmed frame
+        // (sourceSize), so trimming doesn't move it. Relative to the packed
+        // rect it would shift with every frame's trim offset.
+        if let Some((x, y)) = settings.pivot {
+            frame += &format!(",\n      \"pivot\": {{\"x\": {}, \"y\": {}}}", x, y);
+        }
+        frame += "\n    }";
+        frames.push(frame);
+    }
+
+    let sheet = sheet_name(image_name, settings);
+    let mut meta = format!(
+        "    \"image\": {},\n    \"format\": {},\n    \"size\": {{\"w\": {}, \"h\": {}}}",
+        json_string(&sheet),
+        json_string(pixel_format(&sheet, settings)),
+        layout.width,
+        layout.height
+    );
+    if let Some(page) = layout.page {
+        meta += &format!(",\n    \"page\": {}", page);
+    }
+    if settings.stagger {
+        meta += &format!(
+            ",\n    \"stagger\": {{\"rows\": \"odd\", \"offset\": {}}}",
+            crate::stagger_offset(layout)
+        );
+    }
+    // A single factor, or one per axis when --final-size stretched the sheet.
+    match layout.scale {
+        (x, y) if x == y => meta += &format!(",\n    \"scale\": {}", x),
+        (x, y) => meta += &format!(",\n    \"scale\": {{\"x\": {}, \"y\": {}}}", x, y),
+    }
+    if let Some(scale) = layout.sprite_scale {
+        meta += &format!(",\n    \"spriteScale\": {}", scale);
+    }
+    if !frame_tags.is_empty() {
+        let tags: Vec<String> = frame_tags
+            .iter()
+            .map(|tag| {
+                format!(
+                    "      {{\"name\": {}, \"from\": {}, \"to\": {}, \"direction\": {}}}",
+                    json_string(&tag.name),
+                    tag.from,
+                    tag.to,
+                    json_string(&tag.direction)
+                )
+            })
+            .collect();
+        meta += &format!(",\n    \"frameTags\": [\n{}\n    ]", tags.join(",\n"));
+    }
+    if settings.folder_groups {
+        let groups: Vec<String> = group_rects(layout, images)
+            .iter()
+            .map(|group| {
+                let [x, y, w, h] = rect_coords(layout, group.rect, settings);
+                let indices: Vec<String> = group.frames.iter().map(|index| index.to_string()).collect();
+                format!(
+                    "      {{\"name\": {}, \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}, \"frames\": [{}]}}",
+                    json_string(group.name),
+                    x,
+                    y,
+                    w,
+                    h,
+                    indices.join(", ")
+                )
+            })
+            .collect();
+        meta += &format!(",\n    \"groups\": [\n{}\n    ]", groups.join(",\n"));
+    }
+    if !settings.layers.is_empty() {
+        let layers: Vec<String> = settings
+            .layers
+            .iter()
+            .map(|layer| {
+                let file_name = crate::layers::layer_file_name(image_name, layer);
+                format!("{}: {}", json_string(layer), json_string(&file_name))
+            })
+            .collect();
+        meta += &format!(",\n    \"layers\": {{{}}}", layers.join(", "));
+    }
+
+    let mut animations = String::new();
+    if settings.animations {
+        let groups: Vec<String> = animation_groups(images)
+            .iter()
+            .map(|(name, indices)| {
+                let indices: Vec<String> = indices.iter().map(|index| index.to_string()).collect();
+                format!("    {}: [{}]", json_string(name), indices.join(", "))
+            })
+            .collect();
+        animations = if groups.is_empty() {
+            "  \"animations\": {},\n".to_string()
+        } else {
+            format!("  \"animations\": {{\n{}\n  }},\n", groups.join(",\n"))
+        };
+    }
+
+    let premultiplied = match crate::premultiplied_sheet(settings) {
+        true => "  \"premultipliedAlpha\": true,\n",
+        false => "",
+    };
+    let (open, close) = if array { ('[', ']') } else { ('{', '}') };
+    format!(
+        "{{\n{}  \"frames\": {}\n{}\n  {},\n{}  \"meta\": {{\n{}\n  }}\n}}\n",
+        premultiplied,
+        open,
+        frames.join(",\n"),
+        close,
+        animations,
+        meta
+    )
+}
+
+// Same structure as the JSON atlas, plus the cell size in `meta`. TOML basic
+// strings share JSON's escapes, so names are quoted with json_string.
+fn write_toml(
+    layout: &Layout,
+    images: &[Sprite],
+    frame_tags: &[FrameTag],
+    image_name: &str,
+    settings: &Settings,
+) -> String {
+    // Top-level keys have to come before the first table.
+    let mut toml = match crate::premultiplied_sheet(settings) {
+        true => "premultipliedAlpha = true\n\n".to_string(),
+        false => String::new(),
+    };
+    for index in entry_order(layout, images, settings) {
+        let sprite = &images[index];
+        let rect = frame_rect(layout, sprite, index, settings);
+        let [x, y, w, h] = rect_coords(layout, rect, settings);
+        toml += &format!(
+            "[frames.{}]\nframe = {{ x = {}, y = {}, w = {}, h = {} }}\n",
+            json_string(&frame_key(&sprite.name, index, settings)),
+            x,
+            y,
+            w,
+            h
+        );
+        if let Some(color) = sprite.avg_color {
+            toml += &format!(
+                "avgColor = \"#{:02X}{:02X}{:02X}\"\n",
+                color[0], color[1], color[2]
+            );
+        }
+        if let Some(duration) = sprite.duration {
+            toml += &format!("duration = {}\n", duration);
+        }
+        if let Some(hash) = sprite.hash {
+            toml += &format!("hash = \"{}\"\n", short_hash(hash));
+        }
+        if sprite.angle != 0.0 {
+            toml += &format!("angle = {}\n", sprite.angle);
+        }
+        if let Some(([x, y], angle)) = ring_placement(layout, index, settings) {
+            toml += &format!(
+                "ring = {{ center = {{ x = {}, y = {} }}, angle = {} }}\n",
+                x, y, angle
+            );
+        }
+        if settings.compute_bounds && !sprite.placeholder {
+            let (x, y, w, h) = bounds_in_cell(layout, sprite, index, settings);
+            toml += &format!(
+                "opaqueBounds = {{ x = {}, y = {}, w = {}, h = {} }}\n",
+                x, y, w, h
+            );
+        }
+        if sprite.mips.len() > 1 {
+            let levels: Vec<String> = mip_rects(layout, sprite, index, settings)
+                .iter()
+                .map(|[x, y, w, h]| format!("{{ x = {}, y = {}, w = {}, h = {} }}", x, y, w, h))
+                .collect();
+            toml += &format!("mips = [{}]\n", levels.join(", "));
+        }
+        toml += "\n";
+    }
+
+    if settings.animations {
+        toml += "[animations]\n";
+        for (name, indices) in animation_groups(images) {
+            let indices: Vec<String> = indices.iter().map(|index| index.to_string()).collect();
+            toml += &format!("{} = [{}]\n", json_string(&name), indices.join(", "));
+        }
+        toml += "\n";
+    }
+
+    let sheet = sheet_name(image_name, settings);
+    toml += &format!(
+        "[meta]\nimage = {}\nformat = {}\nsize = {{ w = {}, h = {} }}\ncell = {{ w = {}, h = {} }}\n",
+        json_string(&sheet),
+        json_string(pixel_format(&sheet, settings)),
+        layout.width,
+        layout.height,
+        layout.cell_width,
+        layout.cell_height
+    );
+    if let Some(page) = layout.page {
+        toml += &format!("page = {}\n", page);
+    }
+    if settings.stagger {
+        toml += &format!(
+            "stagger = {{ rows = \"odd\", offset = {} }}\n",
+            crate::stagger_offset(layout)
+        );
+    }
+    match layout.scale {
+        (x, y) if x == y => toml += &format!("scale = {}\n", x),
+        (x, y) => toml += &format!("scale = {{ x = {}, y = {} }}\n", x, y),
+    }
+    if let Some(scale) = layout.sprite_scale {
+        toml += &format!("spriteScale = {}\n", scale);
+    }
+    if !settings.layers.is_empty() {
+        let layers: Vec<String> = settings
+            .layers
+            .iter()
+            .map(|layer| {
+                let file_name = crate::layers::layer_file_name(image_name, layer);
+                format!("{} = {}", json_string(layer), json_string(&file_name))
+            })
+            .collect();
+        toml += &format!("layers = {{ {} }}\n", layers.join(", "));
+    }
+    for tag in frame_tags {
+        toml += &format!(
+            "\n[[meta.frameTags]]\nname = {}\nfrom = {}\nto = {}\ndirection = {}\n",
+            json_string(&tag.name),
+            tag.from,
+            tag.to,
+            json_string(&tag.direction)
+        );
+    }
+    if settings.folder_groups {
+        for group in group_rects(layout, images) {
+            let [x, y, w, h] = rect_coords(layout, group.rect, settings);
+            let indices: Vec<String> = group.frames.iter().map(|index| index.to_string()).collect();
+            toml += &format!(
+                "\n[[meta.groups]]\nname = {}\nrect = {{ x = {}, y = {}, w = {}, h = {} }}\nframes = [{}]\n",
+                json_string(group.name),
+                x,
+                y,
+                w,
+                h,
+                indices.join(", ")
+            );
+        }
+    }
+    toml
+}
+
+// A self-contained review page: the sheet with a hotspot over every frame
+// that shows its name and rect on hover and click.
+fn write_html(layout: &Layout, images: &[Sprite], image_name: &str, settings: &Settings) -> String {
+    let hotspots: Vec<String> = images
+        .iter()
+        .enumerate()
+        .take(layout.positions.len())
+        .filter(|(_, sprite)| !sprite.placeholder)
+        .map(|(index, sprite)| {
+            let (x, y, width, height) = frame_rect(layout, sprite, index, settings);
+            let label = html_escape(&format!(
+                "{} ({}, {}, {}x{})",
+                frame_key(&sprite.name, index, settings),
+                x,
+                y,
+                width,
+                height
+            ));
+            format!(
+                "<a style=\"left:{}px;top:{}px;width:{}px;height:{}px\" title=\"{}\" onclick=\"show(this)\"></a>",
+                x, y, width, height, label
+            )
+        })
+        .collect();
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{name}</title>
+<style>
+body {{ font-family: sans-serif; background: #333; color: #eee; }}
+#sheet {{ position: relative; display: inline-block; background: repeating-conic-gradient(#777 0 25%, #999 0 50%) 0 0 / 16px 16px; }}
+#sheet img {{ display: block; image-rendering: pixelated; }}
+#sheet a {{ position: absolute; box-sizing: border-box; cursor: pointer; }}
+#sheet a:hover, #sheet a.selected {{ outline: 1px solid #0ff; background: rgba(0, 255, 255, 0.15); }}
+</style>
+</head>
+<body>
+<p id=\"info\">{name}: {width}x{height}, scale {scale}, {format}, {count} frames</p>
+<div id=\"sheet\">
+<img src=\"{name}\" width=\"{width}\" height=\"{height}\" alt=\"\">
+{hotspots}
+</div>
+<script>
+function show(hotspot) {{
+  document.querySelectorAll('#sheet a.selected').forEach(a => a.classList.remove('selected'));
+  hotspot.classList.add('selected');
+  document.getElementById('info').textContent = hotspot.title;
+}}
+</script>
+</body>
+</html>
+",
+        name = html_escape(image_name),
+        width = layout.width,
+        height = layout.height,
+        scale = match layout.scale {
+            (x, y) if x == y => x.to_string(),
+            (x, y) => format!("{}x{}", x, y),
+        },
+        format = pixel_format(image_name, settings),
+        count = hotspots.len(),
+        hotspots = hotspots.join("\n")
+    )
+}
+
+fn html_escape(str: &str) -> String {
+    str.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// The packed rect of a frame in sheet pixels: the whole cell, or the sprite
+// itself when it was trimmed or carries mips.
+fn frame_rect(
+    layout: &Layout,
+    sprite: &Sprite,
+    index: usize,
+    settings: &Settings,
+) -> (u32, u32, u32, u32) {
+    if sprite.trim.is_some() || !sprite.mips.is_empty() {
+        let (x, y) = crate::align_in_cell(layout, &sprite.image, index, settings);
+        return match sprite.mips.first() {
+            Some(level) => (x, y, level.2, level.3),
+            None => (x, y, sprite.image.width(), sprite.image.height()),
+        };
+    }
+    let position = layout.positions[index];
+    let (width, height) = layout.frame_size(index);
+    (position.0, position.1, width, height)
+}
+
+// The bounding box of the frame's non-transparent pixels relative to its cell
+// (--compute-bounds), all zero for an empty frame. With --opaque-threshold
+// only pixels at least that opaque count.
+fn bounds_in_cell(
+    layout: &Layout,
+    sprite: &Sprite,
+    index: usize,
+    settings: &Settings,
+) -> (u32, u32, u32, u32) {
+    let min_alpha = settings.opaque_threshold.unwrap_or(1);
+    let Some((x, y, width, height)) = crate::trim::solid_bounds(&sprite.image, min_alpha) else {
+        return (0, 0, 0, 0);
+    };
+    let origin = crate::align_in_cell(layout, &sprite.image, index, settings);
+    let position = layout.positions[index];
+    (
+        origin.0 - position.0 + x,
+        origin.1 - position.1 + y,
+        width,
+        height,
+    )
+}
+
+// The sheet rects of the frame's mip levels, smallest last.
+fn mip_rects(
+    layout: &Layout,
+    sprite: &Sprite,
+    index: usize,
+    settings: &Settings,
+) -> Vec<[String; 4]> {
+    let origin = crate::align_in_cell(layout, &sprite.image, index, settings);
+    sprite.mips[1..]
+        .iter()
+        .map(|&(x, y, width, height)| {
+            rect_coords(
+                layout,
+                (origin.0 + x, origin.1 + y, width, height),
+                settings,
+            )
+        })
+        .collect()
+}
+
+struct FrameGroup<'a> {
+    name: &'a str,
+    rect: (u32, u32, u32, u32),
+    frames: Vec<usize>,
+}
+
+// The pixel format of the saved sheet, with libGDX's names: distance fields
+// are single-channel, 16-bit canvases stay 16-bit in PNG and TIFF files, and
+// everything else is decoded as 8-bit RGBA.
+fn pixel_format(image_name: &str, settings: &Settings) -> &'static str {
+    let extension = std::path::Path::new(image_name)
+        .extension()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let sixteen_bit = settings.bit_depth == Some(16)
+        && settings.palette_path.is_none()
+        && ["png", "tif", "tiff"].iter().any(|name| extension == *name);
+    match (settings.color_type, sixteen_bit) {
+        (None, _) if settings.sdf.is_some() => "Intensity",
+        (Some(crate::PngColorType::Gray), false) => "Intensity",
+        (Some(crate::PngColorType::Gray), true) => "Intensity16",
+        (Some(crate::PngColorType::GrayAlpha), false) => "LuminanceAlpha",
+        (Some(crate::PngColorType::GrayAlpha), true) => "LuminanceAlpha1616",
+        (Some(crate::PngColorType::Rgb), false) => "RGB888",
+        (Some(crate::PngColorType::Rgb), true) => "RGB161616",
+        (_, true) => "RGBA16161616",
+        (_, false) => "RGBA8888",
+    }
+}
+
+// Every --folder-groups group with the bounding box of its frames' cells.
+fn group_rects<'a>(layout: &Layout, images: &'a [Sprite]) -> Vec<FrameGroup<'a>> {
+    let names = images.iter().take(layout.positions.len());
+    crate::groups::frame_groups(names.map(|sprite| sprite.name.as_str()))
+        .into_iter()
+        .map(|(name, indices)| {
+            let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+            for &index in indices.iter() {
+                let (x, y) = layout.positions[index];
+                let (width, height) = layout.frame_size(index);
+                (left, top) = (left.min(x), top.min(y));
+                (right, bottom) = (right.max(x + width), bottom.max(y + height));
+            }
+            FrameGroup {
+                name,
+                rect: (left, top, right - left, bottom - top),
+                frames: indices,
+            }
+        })
+        .collect()
+}
+
+// A frame rect in pixels, or with --coords normalized as fractions of the
+// sheet size (UVs).
+fn rect_coords(layout: &Layout, rect: (u32, u32, u32, u32), settings: &Settings) -> [String; 4] {
+    let (x, y, width, height) = rect;
+    if !settings.normalized_coords {
+        return [x, y, width, height].map(|value| value.to_string());
+    }
+    let sheet_width = layout.width.max(1) as f64;
+    let sheet_height = layout.height.max(1) as f64;
+    [
+        x as f64 / sheet_width,
+        y as f64 / sheet_height,
+        width as f64 / sheet_width,
+        height as f64 / sheet_height,
+    ]
+    .map(|value| format!("{:?}", value))
+}
+
+// Where the middle of the frame's cell is on the sheet and its angle on the
+// ring, for --layout radial.
+fn ring_placement(
+    layout: &Layout,
+    index: usize,
+    settings: &Settings,
+) -> Option<([String; 2], f32)> {
+    let angle = *layout.ring_angles.get(index)?;
+    let (x, y) = layout.positions[index];
+    let center = [
+        x as f64 + layout.cell_width as f64 / 2.0,
+        y as f64 + layout.cell_height as f64 / 2.0,
+    ];
+    let center = match settings.normalized_coords {
+        true => [
+            center[0] / layout.width.max(1) as f64,
+            center[1] / layout.height.max(1) as f64,
+        ],
+        false => center,
+    };
+    Some((center.map(|value| format!("{:?}", value)), angle))
+}
+
+// The image the frame rects refer to: with --layers the first layer's sheet,
+// which the other layers' sheets line up with.
+fn sheet_name(image_name: &str, settings: &Settings) -> String {
+    match settings.layers.first() {
+        Some(layer) => crate::layers::layer_file_name(image_name, layer),
+        None => image_name.to_string(),
+    }
+}
+
+// The first 8 hex digits of the frame's content hash.
+fn short_hash(hash: u64) -> String {
+    format!("{:016x}", hash)[..8].to_string()
+}
+
+// Grid constants for C code, plus the sheet's image name, scale and pixel
+// format. The macro prefix is the image name without its extension,
+// uppercased, with anything that isn't valid in an identifier replaced by `_`.
+fn write_c_header(layout: &Layout, image_name: &str, settings: &Settings) -> String {
+    let stem = std::path::Path::new(image_name).with_extension("");
+    let mut prefix: String = stem
+        .to_string_lossy()
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if prefix.is_empty() || prefix.starts_with(|char: char| char.is_ascii_digit()) {
+        prefix.insert(0, '_');
+    }
+
+    let mut header = format!("#ifndef {0}_H\n#define {0}_H\n\n", prefix);
+    for (name, value) in [
+        ("COLUMNS", layout.columns),
+        ("ROWS", layout.rows),
+        ("CELL_WIDTH", layout.cell_width),
+        ("CELL_HEIGHT", layout.cell_height),
+        ("FRAME_COUNT", layout.positions.len() as u32),
+        ("WIDTH", layout.width),
+        ("HEIGHT", layout.height),
+    ] {
+        header += &format!("#define {}_{} {}\n", prefix, name, value);
+    }
+    header += &format!("#define {}_SCALE_X {:?}f\n", prefix, layout.scale.0);
+    header += &format!("#define {}_SCALE_Y {:?}f\n", prefix, layout.scale.1);
+    for (name, value) in [
+        ("IMAGE", image_name),
+        ("FORMAT", pixel_format(image_name, settings)),
+    ] {
+        header += &format!("#define {}_{} {}\n", prefix, name, c_string(value));
+    }
+    header += &format!("\n#endif /* {}_H */\n", prefix);
+    header
+}
+
+// A C string literal; control characters become 3-digit octal escapes, which
+// can't swallow a following digit.
+fn c_string(str: &str) -> String {
+    let mut literal = String::from("\"");
+    for char in str.chars() {
+        match char {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(char);
+            }
+            char if (char as u32) < 0x20 => literal += &format!("\\{:03o}", char as u32),
+            char => literal.push(char),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+// Groups frame indices by their animation_name.
+fn animation_groups(images: &[Sprite]) -> Vec<(String, Vec<usize>)> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, sprite) in images.iter().enumerate() {
+        if sprite.placeholder {
+            continue;
+        }
+        let Some(name) = animation_name(&sprite.name) else {
+            continue;
+        };
+        match groups.iter_mut().find(|group| group.0 == name) {
+            Some(group) => group.1.push(index),
+            None => groups.push((name, vec![index])),
+        }
+    }
+    groups
+}
+
+// The part of the file name before its trailing frame number, so `walk_0.png`
+// and `walk_1.png` belong to the `walk` animation. Names without a number
+// don't belong to any animation.
+pub fn animation_name(file_name: &str) -> Option<String> {
+    let stem = std::path::Path::new(file_name).with_extension("");
+    let stem = stem.to_string_lossy();
+    let prefix = stem.trim_end_matches(|char: char| char.is_ascii_digit());
+    if prefix.len() == stem.len() {
+        return None;
+    }
+    Some(prefix.trim_end_matches(['_', '-', ' ', '.']).to_string())
+}
+
+// Little-endian u16 values: frame count, cell width, cell height, then x, y,
+// w, h for every frame.
+fn write_bin(layout: &Layout) -> Result<Vec<u8>, SpritesheetErr> {
+    let to_u16 = |value: u32| {
+        u16::try_from(value).map_err(|_| {
+            SpritesheetErr::InvalidSettings(
+                "binary atlas values must be smaller than 65536".to_string(),
+            )
+        })
+    };
+
+    let mut values = vec![
+        to_u16(layout.positions.len() as u32)?,
+        to_u16(layout.cell_width)?,
+        to_u16(layout.cell_height)?,
+    ];
+    for (index, position) in layout.positions.iter().enumerate() {
+        let (width, height) = layout.frame_size(index);
+        values.push(to_u16(position.0)?);
+        values.push(to_u16(position.1)?);
+        values.push(to_u16(width)?);
+        values.push(to_u16(height)?);
+    }
+
+    Ok(values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect())
+}
+
+// A FlatBuffer for this schema, so runtimes can read rects straight from the
+// mapped file:
+//
+//     struct Rect { x: uint; y: uint; w: uint; h: uint; }
+//     table Atlas {
+//       frame_count: uint; cell_width: uint; cell_height: uint; frames: [Rect];
+//       width: uint; height: uint; scale_x: float; scale_y: float;
+//     }
+//     root_type Atlas;
+//     file_identifier "SPSH";
+//
+// The sheet size and scale come after the frames, so readers built for the
+// first four fields still work. The layout never changes, so the buffer is
+// written directly: root offset, identifier, vtable, table, then the vector
+// of rects. Everything is a little-endian u32 (or f32) except the vtable's
+// u16 entries, and all of it 4-aligned.
+fn write_flatbuffer(layout: &Layout) -> Vec<u8> {
+    const FIELD_COUNT: u16 = 8;
+    const VTABLE_START: u32 = 8;
+    const VTABLE_SIZE: u16 = 4 + FIELD_COUNT * 2;
+    const TABLE_START: u32 = VTABLE_START + VTABLE_SIZE as u32;
+    const TABLE_SIZE: u16 = 4 + FIELD_COUNT * 4;
+    const FRAMES_FIELD: u32 = 16;
+
+    let mut buffer = Vec::new();
+    buffer.extend(TABLE_START.to_le_bytes());
+    buffer.extend(b"SPSH");
+
+    // Field offsets within the table, after its 4-byte vtable offset.
+    buffer.extend(VTABLE_SIZE.to_le_bytes());
+    buffer.extend(TABLE_SIZE.to_le_bytes());
+    for field in 0..FIELD_COUNT {
+        buffer.extend((4 + field * 4).to_le_bytes());
+    }
+
+    buffer.extend(((TABLE_START - VTABLE_START) as i32).to_le_bytes());
+    buffer.extend((layout.positions.len() as u32).to_le_bytes());
+    buffer.extend(layout.cell_width.to_le_bytes());
+    buffer.extend(layout.cell_height.to_le_bytes());
+    // The vector starts right after the table, relative to this field.
+    buffer.extend((TABLE_SIZE as u32 - FRAMES_FIELD).to_le_bytes());
+    buffer.extend(layout.width.to_le_bytes());
+    buffer.extend(layout.height.to_le_bytes());
+    buffer.extend(layout.scale.0.to_le_bytes());
+    buffer.extend(layout.scale.1.to_le_bytes());
+
+    buffer.extend((layout.positions.len() as u32).to_le_bytes());
+    for (index, position) in layout.positions.iter().enumerate() {
+        let (width, height) = layout.frame_size(index);
+        for value in [position.0, position.1, width, height] {
+            buffer.extend(value.to_le_bytes());
+        }
+    }
+    buffer
+}
+
+// Spine's text atlas. Grid frames are never rotated or trimmed, so every
+// region's size and orig are the cell and its offset is zero. Region names drop
+// the file extension, as Spine looks attachments up without it, unless
+// --rename-pattern gives them explicitly.
+fn write_spine(
+    layout: &Layout,
+    images: &[Sprite],
+    image_name: &str,
+    settings: &Settings,
+) -> String {
+    let mut atlas = format!(
+        "\n{}\nsize: {},{}\nformat: {}\nfilter: Linear,Linear\nrepeat: none\n",
+        image_name,
+        layout.width,
+        layout.height,
+        pixel_format(image_name, settings)
+    );
+    if crate::premultiplied_sheet(settings) {
+        atlas += "pma: true\n";
+    }
+    for index in entry_order(layout, images, settings) {
+        let (sprite, position) = (&images[index], &layout.positions[index]);
+        let name = match settings.rename_pattern {
+            Some(_) => frame_key(&sprite.name, index, settings),
+            None => stem(&sprite.name),
+        };
+        let (width, height) = layout.frame_size(index);
+        let rotate = match settings.no_rotation {
+            true => "",
+            false => "\n  rotate: false",
+        };
+        atlas.push_str(&format!(
+            "{}{}\n  xy: {}, {}\n  size: {}, {}\n  orig: {}, {}\n  offset: 0, 0\n  index: -1\n",
+            name, rotate, position.0, position.1, width, height, width, height
+        ));
+    }
+    atlas
+}
+
+// AngelCode BMFont text descriptor for glyphs named by their codepoint,
+// `65.png` or `U+0041.png` for `A`. Every glyph advances by its width and the
+// line is as tall as the cell; kerning pairs aren't written. Frames whose name
+// isn't a codepoint are left out with a warning.
+fn write_bmfont(
+    layout: &Layout,
+    images: &[Sprite],
+    image_name: &str,
+    settings: &Settings,
+) -> String {
+    let mut chars = Vec::new();
+    let mut unnamed = Vec::new();
+    for index in entry_order(layout, images, settings) {
+        let sprite = &images[index];
+        if sprite.placeholder {
+            continue;
+        }
+        let Some(codepoint) = codepoint(&sprite.name) else {
+            unnamed.push(sprite.name.as_str());
+            continue;
+        };
+        let (x, y, width, height) = frame_rect(layout, sprite, index, settings);
+        chars.push(format!(
+            "char id={} x={} y={} width={} height={} xoffset=0 yoffset=0 xadvance={} page=0 chnl=15\n",
+            codepoint, x, y, width, height, width
+        ));
+    }
+    if !unnamed.is_empty() {
+        let message = format!(
+            "left {} frames out of the bmfont atlas, their names aren't codepoints: {}",
+            unnamed.len(),
+            unnamed.join(", ")
+        );
+        crate::warn(settings, message, None);
+    }
+
+    let face = stem(image_name);
+    let line_height = layout.cell_height;
+    format!(
+        "info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=1 aa=1 padding=0,0,0,0 spacing=0,0\ncommon lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0\npage id=0 file=\"{}\"\nchars count={}\n{}",
+        face,
+        line_height,
+        line_height,
+        line_height,
+        layout.width,
+        layout.height,
+        image_name,
+        chars.len(),
+        chars.concat()
+    )
+}
+
+// The codepoint a glyph file is named after, in decimal or as `U+` hex.
+fn codepoint(file_name: &str) -> Option<u32> {
+    let name = stem(file_name);
+    let name = name.rsplit('/').next().unwrap_or_default();
+    let codepoint = match name.strip_prefix("U+").or_else(|| name.strip_prefix("u+")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => name.parse().ok()?,
+    };
+    char::from_u32(codepoint).map(u32::from)
+}
+
+// The atlas key of a frame: its file name, or the --rename-pattern with
+// `{name}`, `{stem}` and `{index}` filled in. Packing always uses the real file
+// names, only the metadata is renamed.
+pub fn frame_key(name: &str, index: usize, settings: &Settings) -> String {
+    let Some(pattern) = &settings.rename_pattern else {
+        return name.to_string();
+    };
+    // One pass, so placeholders inside the substituted names stay as they are.
+    // The parser only accepts braces that form one of the three placeholders.
+    let mut key = String::new();
+    let mut rest = pattern.as_str();
+    while let Some(start) = rest.find('{') {
+        key += &rest[..start];
+        let end = start + rest[start..].find('}').unwrap() + 1;
+        match &rest[start..end] {
+            "{name}" => key += name,
+            "{stem}" => key += &stem(name),
+            _ => key += &index.to_string(),
+        }
+        rest = &rest[end..];
+    }
+    key + rest
+}
+
+fn stem(name: &str) -> String {
+    std::path::Path::new(name)
+        .with_extension("")
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn json_string(str: &str) -> String {
+    let mut escaped = String::from("\"");
+    for char in str.chars() {
+        match char {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            char if (char as u32) < 0x20 => escaped += &format!("\\u{:04x}", char as u32),
+            char => escaped.push(char),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,90 @@
+use crate::{json, print_error, try_create_spritesheet, SpritesheetErr};
+
+// Runs every job of a JSON array like
+//
+//     [{"name": "ui", "input": "ui", "output": "ui.png", "auto": true}]
+//
+// as if its keys were given on the command line: `"key": value` becomes
+// `--key value`, `true` a bare `--key` and arrays a repeated one. `auto` is
+// passed as is and `name` only labels the job. Paths are relative to the jobs
+// file. Failed jobs don't stop the batch, but make it fail at the end.
+pub fn run_batch(args: &[String]) -> Result<(), SpritesheetErr> {
+    let [path] = args else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: batch <jobs.json>".to_string(),
+        ));
+    };
+    let error = |message: &str| SpritesheetErr::ImportError(format!("{}: {}", path, message));
+    let contents = std::fs::read_to_string(path).map_err(|_| error("can't read jobs"))?;
+    let jobs = json::parse(&contents).ok_or_else(|| error("invalid json"))?;
+    let jobs = jobs
+        .as_array()
+        .ok_or_else(|| error("expected an array of jobs"))?;
+
+    let mut job_args = Vec::new();
+    for (index, job) in jobs.iter().enumerate() {
+        let job_error = |message: &str| error(&format!("job {}: {}", index + 1, message));
+        let json::JsonValue::Object(entries) = job else {
+            return Err(job_error("expected an object"));
+        };
+        let mut name = (index + 1).to_string();
+        let mut arguments = Vec::new();
+        for (key, value) in entries {
+            if key == "name" {
+                name = value
+                    .as_str()
+                    .ok_or_else(|| job_error("name must be a string"))?
+                    .to_string();
+                continue;
+            }
+            let flag = if key == "auto" {
+                key.clone()
+            } else {
+                format!("--{}", key)
+            };
+            let values = match value {
+                json::JsonValue::Array(values) => values.as_slice(),
+                value => std::slice::from_ref(value),
+            };
+            for value in values {
+                match value {
+                    json::JsonValue::Bool(true) => arguments.push(flag.clone()),
+                    json::JsonValue::Bool(false) => (),
+                    json::JsonValue::String(value) => {
+                        arguments.extend([flag.clone(), value.clone()])
+                    }
+                    json::JsonValue::Number(value) => {
+                        arguments.extend([flag.clone(), value.to_string()])
+                    }
+                    _ => return Err(job_error(&format!("unsupported value for {}", key))),
+                }
+            }
+        }
+        job_args.push((name, arguments));
+    }
+
+    let base = std::path::Path::new(path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    if !base.as_os_str().is_empty() {
+        std::env::set_current_dir(base).map_err(|_| error("can't enter the jobs directory"))?;
+    }
+    let mut failed = 0;
+    for (name, arguments) in job_args.iter() {
+        println!("Job {}:", name);
+        match try_create_spritesheet(arguments) {
+            Ok(()) => println!("Job {} done", name),
+            Err(error) => {
+                print_error(&error);
+                println!("Job {} failed", name);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(SpritesheetErr::BatchFailed(failed, job_args.len()));
+    }
+    println!("All {} jobs done", job_args.len());
+    Ok(())
+}
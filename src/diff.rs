@@ -0,0 +1,65 @@
+use image::GenericImageView;
+
+use crate::SpritesheetErr;
+
+pub fn diff_sheets(args: &[String]) -> Result<(), SpritesheetErr> {
+    let [first_path, second_path] = args else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: diff <first.png> <second.png>".to_string(),
+        ));
+    };
+    let first = open_image(first_path)?;
+    let second = open_image(second_path)?;
+
+    let mut differs = first.dimensions() != second.dimensions();
+    if differs {
+        println!(
+            "Dimensions differ: {}x{} vs {}x{}",
+            first.width(),
+            first.height(),
+            second.width(),
+            second.height()
+        );
+    }
+
+    let width = first.width().min(second.width());
+    let height = first.height().min(second.height());
+    let mut differing_pixels = 0u64;
+    let mut bounds = (u32::MAX, u32::MAX, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if first.get_pixel(x, y) != second.get_pixel(x, y) {
+                differing_pixels += 1;
+                bounds = (
+                    bounds.0.min(x),
+                    bounds.1.min(y),
+                    bounds.2.max(x),
+                    bounds.3.max(y),
+                );
+            }
+        }
+    }
+
+    if differing_pixels > 0 {
+        differs = true;
+        println!("Differing pixels: {}", differing_pixels);
+        println!(
+            "Bounding box: x={} y={} w={} h={}",
+            bounds.0,
+            bounds.1,
+            bounds.2 - bounds.0 + 1,
+            bounds.3 - bounds.1 + 1
+        );
+    }
+
+    if differs {
+        Err(SpritesheetErr::ImagesDiffer)
+    } else {
+        println!("Images are identical");
+        Ok(())
+    }
+}
+
+fn open_image(path: &str) -> Result<image::DynamicImage, SpritesheetErr> {
+    image::open(path).map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", path)))
+}
@@ -0,0 +1,152 @@
+use crate::ImageData;
+
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const EXIF_IFD_POINTER: u16 = 0x8769;
+
+// Orders the frames by when they were taken: the EXIF DateTimeOriginal of
+// JPEG, PNG, WebP and TIFF files, or the modification time of files without
+// one. EXIF times have no zone and are compared as if they were UTC. Frames
+// with neither keep their order after the others.
+pub fn sort_by_capture_time(images_data: &mut [ImageData]) {
+    images_data.sort_by_cached_key(|image_info| {
+        let time = capture_time(image_info);
+        (time.is_none(), time)
+    });
+}
+
+fn capture_time(image_info: &ImageData) -> Option<i64> {
+    if image_info.placeholder {
+        return None;
+    }
+    let exif_time = image_info
+        .raw_data()
+        .ok()
+        .and_then(|data| exif_block(&data).and_then(date_time_original));
+    exif_time.or_else(|| {
+        if image_info.bytes.is_some() {
+            return None;
+        }
+        let modified = std::fs::metadata(&image_info.path).ok()?.modified().ok()?;
+        let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+        Some(since_epoch.as_secs() as i64)
+    })
+}
+
+// The TIFF structure holding the EXIF tags, wherever the container keeps it.
+fn exif_block(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        return Some(data);
+    }
+    if data.starts_with(&[0xff, 0xd8]) {
+        // JPEG: APP1 segment starting with `Exif\0\0`.
+        let mut offset = 2;
+        while offset + 4 <= data.len() && data[offset] == 0xff {
+            let marker = data[offset + 1];
+            let length = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+            let segment = data.get(offset + 4..offset + 2 + length)?;
+            if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+                return Some(&segment[6..]);
+            }
+            if marker == 0xda {
+                return None;
+            }
+            offset += 2 + length;
+        }
+        return None;
+    }
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut offset = 8;
+        while offset + 8 <= data.len() {
+            let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+            let chunk = data.get(offset + 8..offset + 8 + length)?;
+            if &data[offset + 4..offset + 8] == b"eXIf" {
+                return Some(chunk);
+            }
+            offset += 12 + length;
+        }
+        return None;
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let length =
+                u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let chunk = data.get(offset + 8..offset + 8 + length)?;
+            if &data[offset..offset + 4] == b"EXIF" {
+                // Some writers keep the JPEG-style prefix.
+                return Some(chunk.strip_prefix(b"Exif\0\0").unwrap_or(chunk));
+            }
+            offset += 8 + length + length % 2;
+        }
+    }
+    None
+}
+
+fn date_time_original(tiff: &[u8]) -> Option<i64> {
+    let big_endian = match tiff.get(0..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes: [u8; 2] = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let u32_at = |offset: usize| {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+    // Value field of the entry with the tag, or None.
+    let find_tag = |ifd: usize, tag: u16| {
+        let count = u16_at(ifd)? as usize;
+        (0..count)
+            .map(|index| ifd + 2 + index * 12)
+            .find(|&entry| u16_at(entry) == Some(tag))
+            .map(|entry| (u32_at(entry + 4), entry + 8))
+    };
+
+    let ifd0 = u32_at(4)? as usize;
+    let (_, pointer) = find_tag(ifd0, EXIF_IFD_POINTER)?;
+    let exif_ifd = u32_at(pointer)? as usize;
+    let (count, value) = find_tag(exif_ifd, DATE_TIME_ORIGINAL)?;
+    let count = count? as usize;
+    let offset = match count <= 4 {
+        true => value,
+        false => u32_at(value)? as usize,
+    };
+    let text = std::str::from_utf8(tiff.get(offset..offset + count)?).ok()?;
+    parse_exif_time(text.trim_end_matches('\0'))
+}
+
+// `YYYY:MM:DD HH:MM:SS` to seconds since the Unix epoch.
+fn parse_exif_time(text: &str) -> Option<i64> {
+    let (date, time) = text.trim().split_once(' ')?;
+    let date: Vec<i64> = date
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<i64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (&[year, month, day], &[hour, minute, second]) = (&date[..], &time[..]) else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days from civil date (proleptic Gregorian), March-based years.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
@@ -0,0 +1,93 @@
+use crate::Layout;
+
+// The folder part of a frame name, `hero` for `hero/arm.png`; frames at the
+// top of an input directory belong to no group.
+pub fn group_name(name: &str) -> Option<&str> {
+    name.rsplit_once('/').map(|(group, _)| group)
+}
+
+// The groups in order of their first frame, each with its frame indices.
+pub fn frame_groups<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<(&'a str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let Some(group) = group_name(name) else {
+            continue;
+        };
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((group, vec![index])),
+        }
+    }
+    groups
+}
+
+// Gives every group a block of cells as close to square as possible and
+// places the blocks left to right in shelves, so the frames of one folder
+// stay together. Ungrouped frames are blocks of a single cell. The sheet is
+// about as wide as a square grid of all the cells, or the widest block.
+pub fn layout(names: &[&str], cell: (u32, u32)) -> Layout {
+    let mut blocks: Vec<Vec<usize>> = frame_groups(names.iter().copied())
+        .into_iter()
+        .map(|(_, indices)| indices)
+        .collect();
+    for (index, name) in names.iter().enumerate() {
+        if group_name(name).is_none() {
+            blocks.push(vec![index]);
+        }
+    }
+    // Blocks keep the order of their first frame.
+    blocks.sort_by_key(|indices| indices[0]);
+
+    let block_size = |count: usize| {
+        let columns = (count as f64).sqrt().ceil().max(1.0) as u32;
+        (columns, (count as u32).div_ceil(columns))
+    };
+    let widest = blocks
+        .iter()
+        .map(|indices| block_size(indices.len()).0)
+        .max()
+        .unwrap_or(1);
+    let cells: u32 = blocks
+        .iter()
+        .map(|indices| {
+            let (columns, rows) = block_size(indices.len());
+            columns * rows
+        })
+        .sum();
+    let columns = ((cells as f64).sqrt().ceil() as u32).max(widest);
+
+    let mut positions = vec![(0, 0); names.len()];
+    let (mut x, mut y, mut shelf) = (0, 0, 0);
+    for indices in blocks.iter() {
+        let (block_columns, block_rows) = block_size(indices.len());
+        if x + block_columns > columns {
+            (x, y, shelf) = (0, y + shelf, 0);
+        }
+        for (offset, &index) in indices.iter().enumerate() {
+            let offset = offset as u32;
+            positions[index] = (
+                (x + offset % block_columns) * cell.0,
+                (y + offset / block_columns) * cell.1,
+            );
+        }
+        x += block_columns;
+        shelf = shelf.max(block_rows);
+    }
+    let rows = y + shelf;
+
+    Layout {
+        columns,
+        rows,
+        cell_width: cell.0,
+        cell_height: cell.1,
+        width: columns * cell.0,
+        height: rows * cell.1,
+        positions,
+        scale: (1.0, 1.0),
+        frame_heights: Vec::new(),
+        ring_angles: Vec::new(),
+        page: None,
+        sprite_scale: None,
+        cropped_frames: Vec::new(),
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{get_image_format, sniff_bytes, ImageData, SpritesheetErr};
+use std::io::{Read, Write};
+
+const MAX_REDIRECTS: usize = 5;
+
+pub fn is_url(name: &str) -> bool {
+    name.starts_with("http://") || name.starts_with("https://")
+}
+
+// Downloads an image into memory. The format comes from the URL path, then
+// the Content-Type, then the data itself. Only plain HTTP is supported since
+// no TLS implementation is bundled.
+pub fn fetch_image(url: &str) -> Result<ImageData, SpritesheetErr> {
+    let error = |message: &str| SpritesheetErr::ImportError(format!("{}: {}", url, message));
+    let (body, content_type) = get(url, MAX_REDIRECTS).map_err(|message| error(&message))?;
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.rsplit('/').next().filter(|name| !name.is_empty());
+    let format = name
+        .and_then(|name| std::path::Path::new(name).extension())
+        .and_then(|extension| extension.to_str())
+        .and_then(get_image_format)
+        .or_else(|| content_type.as_deref().and_then(format_from_mime))
+        .or_else(|| sniff_bytes(&body))
+        .ok_or_else(|| error("not a supported image"))?;
+
+    Ok(ImageData {
+        name: name.unwrap_or(url).to_string(),
+        path: std::path::PathBuf::from(url),
+        format,
+        duration: None,
+        bytes: Some(body),
+        placeholder: false,
+        page_break: false,
+        layers: Vec::new(),
+    })
+}
+
+fn format_from_mime(content_type: &str) -> Option<image::ImageFormat> {
+    let mime = content_type.split(';').next()?.trim();
+    match mime.to_ascii_lowercase().as_str() {
+        "image/png" => Some(image::ImageFormat::Png),
+        "image/jpeg" => Some(image::ImageFormat::Jpeg),
+        "image/bmp" => Some(image::ImageFormat::Bmp),
+        "image/webp" => Some(image::ImageFormat::WebP),
+        "image/tiff" => Some(image::ImageFormat::Tiff),
+        _ => None,
+    }
+}
+
+// A minimal HTTP/1.0 GET that follows redirects. Returns the body and the
+// Content-Type header.
+fn get(url: &str, redirects_left: usize) -> Result<(Vec<u8>, Option<String>), String> {
+    if url.starts_with("https://") {
+        return Err("https isn't supported, no TLS implementation is bundled".to_string());
+    }
+    let rest = url.strip_prefix("http://").ok_or("not an http url")?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    let path = path.split('#').next().unwrap_or(path);
+    let address = match authority.contains(':') {
+        true => authority.to_string(),
+        false => format!("{}:80", authority),
+    };
+
+    let mut stream = std::net::TcpStream::connect(&address)
+        .map_err(|error| format!("can't connect to {}: {}", authority, error))?;
+    let _ = stream.set_read_timeout(Some(std::time::Duration::from_secs(30)));
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: image/*\r\nConnection: close\r\n\r\n",
+        path, authority
+    )
+    .map_err(|error| format!("can't send request: {}", error))?;
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .map_err(|error| format!("can't read response: {}", error))?;
+
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("malformed response")?;
+    let head = String::from_utf8_lossy(&response[..header_end]).into_owned();
+    let body = response.split_off(header_end + 4);
+    let mut lines = head.lines();
+    let status: u16 = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("malformed status line")?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let header = |key: &str| {
+        headers
+            .iter()
+            .find(|header| header.0 == key)
+            .map(|header| header.1.clone())
+    };
+
+    match status {
+        200 => {}
+        301 | 302 | 303 | 307 | 308 => {
+            let location = header("location").ok_or("redirect without a location")?;
+            if redirects_left == 0 {
+                return Err("too many redirects".to_string());
+            }
+            let location = resolve_location(&location, authority, path);
+            if location.starts_with("https://") {
+                return Err(format!(
+                    "redirected to {}, but https isn't supported",
+                    location
+                ));
+            }
+            return get(&location, redirects_left - 1);
+        }
+        _ => return Err(format!("server answered {}", status)),
+    }
+
+    let body = match header("transfer-encoding") {
+        Some(encoding) if encoding.eq_ignore_ascii_case("chunked") => {
+            dechunk(&body).ok_or("malformed chunked body")?
+        }
+        _ => body,
+    };
+    Ok((body, header("content-type")))
+}
+
+// Turns a Location header into an absolute URL; relative ones are resolved
+// against the directory of the requested path.
+fn resolve_location(location: &str, authority: &str, path: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    if let Some(rest) = location.strip_prefix("//") {
+        return format!("http://{}", rest);
+    }
+    if location.starts_with('/') {
+        return format!("http://{}{}", authority, location);
+    }
+    let path = path.split('?').next().unwrap_or(path);
+    let directory = &path[..path.rfind('/').map_or(0, |slash| slash + 1)];
+    format!("http://{}{}{}", authority, directory, location)
+}
+
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|window| window == b"\r\n")?;
+        let size = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        let chunk = data.get(line_end + 2..line_end + 2 + size)?;
+        body.extend_from_slice(chunk);
+        data = data.get(line_end + 4 + size..)?;
+    }
+}
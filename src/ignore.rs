@@ -0,0 +1,37 @@
+// Patterns from a `.packerignore` file in the scanned directory. Each
+// non-blank line that doesn't start with `#` is a glob matched against file
+// names, where `*` matches any run of characters and `?` a single one.
+pub fn read_patterns(dir: &std::path::Path) -> Vec<String> {
+    let Ok(contents) = std::fs::read_to_string(dir.join(".packerignore")) else {
+        return Vec::new();
+    };
+    parse_patterns(&contents)
+}
+
+pub fn parse_patterns(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn is_ignored(patterns: &[String], file_name: &str) -> bool {
+    patterns.iter().any(|pattern| matches(pattern, file_name))
+}
+
+pub fn matches(pattern: &str, file_name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = file_name.chars().collect();
+    glob_match(&pattern, &name)
+}
+
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some((char, rest)) => name.first() == Some(char) && glob_match(rest, &name[1..]),
+    }
+}
@@ -0,0 +1,357 @@
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries
+                .iter()
+                .find(|entry| entry.0 == key)
+                .map(|entry| &entry.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_f64()
+            .filter(|value| *value >= 0.0 && *value <= u32::MAX as f64)
+            .map(|value| value as u32)
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+// Arrays and objects nested deeper than this are rejected rather than
+// recursing until the stack overflows.
+const MAX_DEPTH: usize = 128;
+
+pub fn parse(str: &str) -> Option<JsonValue> {
+    let mut parser = Parser {
+        chars: str.chars().collect(),
+        index: 0,
+        depth: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.index == parser.chars.len() {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.index += 1;
+        Some(char)
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_whitespace();
+        if self.next()? == expected {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(char) = self.peek() {
+            if !char.is_whitespace() {
+                break;
+            }
+            self.index += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Option<JsonValue> {
+        self.skip_whitespace();
+        match self.peek()? {
+            '{' | '[' => {
+                if self.depth == MAX_DEPTH {
+                    return None;
+                }
+                self.depth += 1;
+                let value = match self.peek()? {
+                    '{' => self.parse_object(),
+                    _ => self.parse_array(),
+                };
+                self.depth -= 1;
+                value
+            }
+            '"' => self.parse_string().map(JsonValue::String),
+            't' => self.parse_literal("true", JsonValue::Bool(true)),
+            'f' => self.parse_literal("false", JsonValue::Bool(false)),
+            'n' => self.parse_literal("null", JsonValue::Null),
+            _ => self.parse_number(),
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Option<JsonValue> {
+        for expected in literal.chars() {
+            if self.next()? != expected {
+                return None;
+            }
+        }
+        Some(value)
+    }
+
+    // JSON's number grammar: an optional minus, an integer part without
+    // leading zeros, then an optional fraction and exponent.
+    fn parse_number(&mut self) -> Option<JsonValue> {
+        let start = self.index;
+        self.skip_char('-');
+        match self.peek()? {
+            '0' => self.index += 1,
+            '1'..='9' => self.skip_digits(),
+            _ => return None,
+        }
+        if self.skip_char('.') {
+            self.require_digits()?;
+        }
+        if self.skip_char('e') || self.skip_char('E') {
+            let _ = self.skip_char('+') || self.skip_char('-');
+            self.require_digits()?;
+        }
+        let number: String = self.chars[start..self.index].iter().collect();
+        number.parse().ok().map(JsonValue::Number)
+    }
+
+    fn skip_char(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek().is_some_and(|char| char.is_ascii_digit()) {
+            self.index += 1;
+        }
+    }
+
+    fn require_digits(&mut self) -> Option<()> {
+        let start = self.index;
+        self.skip_digits();
+        (self.index > start).then_some(())
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(string),
+                '\\' => match self.next()? {
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'u' => {
+                        let code = self.parse_hex_code()?;
+                        if (0xD800..0xDC00).contains(&code) {
+                            self.parse_literal("\\u", JsonValue::Null)?;
+                            // Only a low surrogate can complete the pair.
+                            let low = self
+                                .parse_hex_code()
+                                .filter(|low| (0xDC00..0xE000).contains(low))?;
+                            let code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            string.push(char::from_u32(code)?);
+                        } else {
+                            string.push(char::from_u32(code)?);
+                        }
+                    }
+                    char => string.push(char),
+                },
+                char => string.push(char),
+            }
+        }
+    }
+
+    fn parse_hex_code(&mut self) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            code = code * 16 + self.next()?.to_digit(16)?;
+        }
+        Some(code)
+    }
+
+    fn parse_array(&mut self) -> Option<JsonValue> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == ']' {
+            self.index += 1;
+            return Some(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                ']' => return Some(JsonValue::Array(values)),
+                _ => return None,
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Option<JsonValue> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek()? == '}' {
+            self.index += 1;
+            return Some(JsonValue::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => continue,
+                '}' => return Some(JsonValue::Object(entries)),
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let value = parse(r#" {"frames": [{"x": 3, "w": 1.5e1}, null, true], "name": "a\"b"} "#);
+        let value = value.unwrap();
+        let frames = value.get("frames").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(frames[0].get("x").and_then(JsonValue::as_u32), Some(3));
+        assert_eq!(frames[0].get("w").and_then(JsonValue::as_f64), Some(15.0));
+        assert!(matches!(frames[1], JsonValue::Null));
+        assert!(matches!(frames[2], JsonValue::Bool(true)));
+        assert_eq!(value.get("name").and_then(JsonValue::as_str), Some("a\"b"));
+        assert!(matches!(parse("[]"), Some(JsonValue::Array(values)) if values.is_empty()));
+        assert!(matches!(parse("{}"), Some(JsonValue::Object(entries)) if entries.is_empty()));
+    }
+
+    #[test]
+    fn strings_round_trip_through_the_atlas_writer() {
+        for string in [
+            "plain",
+            "quote \" and \\ slash",
+            "tab\tline\nfeed\r",
+            "\u{1}",
+            "é 😀",
+        ] {
+            let written = crate::atlas::json_string(string);
+            let parsed = parse(&written).unwrap();
+            assert_eq!(parsed.as_str(), Some(string));
+        }
+        let parsed = parse(r#""\ud83d\ude00 \u00e9""#).unwrap();
+        assert_eq!(parsed.as_str(), Some("😀 é"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for input in [
+            "",
+            "[1, 2",
+            "[1, 2,]",
+            "{\"a\" 1}",
+            "{\"a\": 1,}",
+            "\"unterminated",
+            "\"\\ud83d\"",
+            "\"\\ud83d\\u0041\"",
+            "\"\\u12g4\"",
+            "tru",
+            "[1] 2",
+            "{1: 2}",
+            "1-2",
+            "1.2.3",
+            "+1",
+            "01",
+            "1.",
+            ".5",
+            "1e",
+            "--1",
+            "-",
+        ] {
+            assert!(parse(input).is_none(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_every_number_form() {
+        for (input, expected) in [
+            ("0", 0.0),
+            ("-0", 0.0),
+            ("12", 12.0),
+            ("-3.25", -3.25),
+            ("1e3", 1000.0),
+            ("2E-2", 0.02),
+            ("5e+1", 50.0),
+        ] {
+            assert_eq!(
+                parse(input).and_then(|value| value.as_f64()),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_some());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_none());
+        // Deep enough to overflow the stack without the limit.
+        assert!(parse(&"[".repeat(100_000)).is_none());
+        assert!(parse(&"{\"a\":".repeat(100_000)).is_none());
+    }
+
+    #[test]
+    fn as_u32_rejects_out_of_range_numbers() {
+        assert_eq!(parse("-1").and_then(|value| value.as_u32()), None);
+        assert_eq!(parse("5e9").and_then(|value| value.as_u32()), None);
+        assert_eq!(parse("\"7\"").and_then(|value| value.as_u32()), None);
+    }
+}
@@ -0,0 +1,102 @@
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+const MARGIN: u32 = 1;
+pub const LABEL_HEIGHT: u32 = GLYPH_HEIGHT + 2 * MARGIN;
+
+// A 3x5 bitmap font, one row per byte with the leftmost pixel in bit 2.
+// Lowercase letters use the uppercase glyphs and anything missing is drawn
+// as '?'.
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('@', [0b010, 0b101, 0b111, 0b100, 0b011]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+];
+
+fn glyph(char: char) -> [u8; 5] {
+    let char = char.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|glyph| glyph.0 == char)
+        .or_else(|| GLYPHS.iter().find(|glyph| glyph.0 == '?'))
+        .map(|glyph| glyph.1)
+        .unwrap()
+}
+
+// Draws the text into the LABEL_HEIGHT strip at (x, y), clipped to `width`
+// so long names don't run into the neighbouring cell.
+pub fn draw_label(
+    canvas: &mut DynamicImage,
+    text: &str,
+    x: u32,
+    y: u32,
+    width: u32,
+    color: Rgba<u8>,
+) {
+    for (index, char) in text.chars().enumerate() {
+        let glyph_x = MARGIN + index as u32 * (GLYPH_WIDTH + 1);
+        if glyph_x >= width {
+            break;
+        }
+        for (row, bits) in glyph(char).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                let pixel_x = glyph_x + column;
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 || pixel_x >= width {
+                    continue;
+                }
+                let (canvas_x, canvas_y) = (x + pixel_x, y + MARGIN + row as u32);
+                if canvas.in_bounds(canvas_x, canvas_y) {
+                    canvas.put_pixel(canvas_x, canvas_y, color);
+                }
+            }
+        }
+    }
+}
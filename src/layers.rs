@@ -0,0 +1,116 @@
+use crate::{warn, ImageData, Settings, Sprite, SpritesheetErr};
+
+// The frame name without its layer suffix, `walk_0.png` for
+// `walk_0_normal.png`, and that layer.
+fn split_name<'a>(name: &str, layers: &'a [String]) -> Option<(String, &'a str)> {
+    let (rest, extension) = match name.rsplit_once('.') {
+        Some((rest, extension)) if !extension.contains('/') => (rest, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    layers.iter().find_map(|layer| {
+        let base = rest.strip_suffix(layer.as_str())?.strip_suffix('_')?;
+        Some((format!("{}{}", base, extension), layer.as_str()))
+    })
+}
+
+// Keeps the files of the first --layers layer as the frames, named without
+// their suffix, and attaches each one's files on the other layers, matched by
+// that name regardless of extension. A frame missing one of its layers is an
+// error; files without a layer suffix, or whose frame has no file on the
+// first layer, are skipped with a warning.
+pub fn attach_layers(
+    images_data: Vec<ImageData>,
+    settings: &Settings,
+) -> Result<Vec<ImageData>, SpritesheetErr> {
+    let stem = |name: &str| match name.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') => stem.to_string(),
+        _ => name.to_string(),
+    };
+    let mut frames = Vec::new();
+    let mut others = Vec::new();
+    for mut image_info in images_data {
+        if image_info.page_break || image_info.placeholder {
+            frames.push(image_info);
+            continue;
+        }
+        match split_name(&image_info.name, &settings.layers) {
+            Some((name, layer)) if layer == settings.layers[0] => {
+                image_info.name = name;
+                frames.push(image_info);
+            }
+            Some((name, layer)) => others.push((stem(&name), layer, image_info)),
+            None => {
+                let message = format!(
+                    "{} has none of the --layers suffixes, skipping",
+                    image_info.name
+                );
+                warn(settings, message, Some(&image_info.path.to_string_lossy()));
+            }
+        }
+    }
+
+    for image_info in frames
+        .iter_mut()
+        .filter(|image_info| !image_info.page_break && !image_info.placeholder)
+    {
+        let frame = stem(&image_info.name);
+        for layer in settings.layers[1..].iter() {
+            let found = others
+                .iter()
+                .position(|(name, other, _)| *name == frame && other == layer);
+            let Some(found) = found else {
+                return Err(SpritesheetErr::InvalidSettings(format!(
+                    "{} has no {} layer",
+                    image_info.name, layer
+                )));
+            };
+            image_info.layers.push(others.remove(found).2);
+        }
+    }
+    for (_, _, image_info) in others {
+        let message = format!(
+            "{} has no {} layer, skipping",
+            image_info.name, settings.layers[0]
+        );
+        warn(settings, message, Some(&image_info.path.to_string_lossy()));
+    }
+    Ok(frames)
+}
+
+// `sheet_normal.png` for the normal layer of `sheet.png`.
+pub fn layer_path(path: &std::path::Path, layer: &str) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{}.{}", stem, layer, extension))
+}
+
+pub fn layer_file_name(image_name: &str, layer: &str) -> String {
+    let path = layer_path(std::path::Path::new(image_name), layer);
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into()
+}
+
+// The frames with their image on the `index`th of the other layers, to pack
+// at the same positions.
+pub fn layer_sprites(images: &[Sprite], index: usize) -> Vec<Sprite> {
+    images
+        .iter()
+        .map(|sprite| Sprite {
+            name: sprite.name.clone(),
+            image: match sprite.layers.get(index) {
+                Some(image) => image.clone(),
+                None => sprite.image.clone(),
+            },
+            hash: None,
+            avg_color: None,
+            duration: sprite.duration,
+            placeholder: sprite.placeholder,
+            trim: None,
+            angle: 0.0,
+            mips: Vec::new(),
+            layers: Vec::new(),
+        })
+        .collect()
+}
@@ -0,0 +1,4357 @@
+use std::{io::Write, num::ParseIntError};
+
+use image::{DynamicImage, GenericImage, GenericImageView};
+
+mod analyze;
+mod append;
+mod aseprite;
+mod atlas;
+mod batch;
+mod diff;
+mod exif;
+mod groups;
+mod http;
+mod ignore;
+mod json;
+mod labels;
+mod layers;
+mod manifest;
+mod metadata;
+mod mips;
+mod named_cells;
+mod palette;
+mod patch;
+mod postprocess;
+mod radial;
+mod raw;
+mod reuse;
+mod rotate;
+mod schema;
+mod sdf;
+mod tile;
+mod trim;
+mod unpack;
+mod validate;
+mod watch;
+mod webp;
+mod zip;
+
+// The command-line entry point, called by the binary in src/main.rs.
+pub fn run() {
+    let (pause_flags, args): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg == "--no-pause");
+    let result = match args.first().map(String::as_str) {
+        Some("diff") => diff::diff_sheets(&args[1..]),
+        Some("validate") => validate::validate_atlas(&args[1..]),
+        Some("extract-metadata") => metadata::extract_metadata(&args[1..]),
+        Some("batch") => batch::run_batch(&args[1..]),
+        Some("tile") => tile::tile_image(&args[1..]),
+        Some("unpack") => unpack::unpack_sheet(&args[1..]),
+        Some("atlas-schema") => schema::print_atlas_schema(&args[1..]),
+        _ => try_create_spritesheet(&args),
+    };
+
+    if let Err(error) = result {
+        print_error(&error);
+        // The pause keeps the message readable when the tool was started by
+        // double-clicking it; scripts and pipes exit right away.
+        if pause_flags.is_empty() && std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            std::thread::sleep(std::time::Duration::from_secs(3));
+        }
+        std::process::exit(1);
+    }
+}
+
+fn print_error(error: &SpritesheetErr) {
+    match error {
+        SpritesheetErr::NoImagesFound => println!("Error: no images found"),
+        SpritesheetErr::FilterImages(resolutions) => {
+            println!("Error: filter image error, found {}", resolutions)
+        }
+        SpritesheetErr::ImageSaveError => println!("Error: save image error"),
+        SpritesheetErr::ParseError => println!("Error: parse error"),
+        SpritesheetErr::InvalidArgument(arg) => {
+            println!("Error: invalid argument \"{}\"", arg)
+        }
+        SpritesheetErr::AtlasSaveError => println!("Error: save atlas error"),
+        SpritesheetErr::ImportError(message) => {
+            println!("Error: import error, {}", message)
+        }
+        SpritesheetErr::InvalidSettings(message) => println!("Error: {}", message),
+        SpritesheetErr::LayoutFileError(path) => {
+            println!("Error: can't use layout file {}", path)
+        }
+        SpritesheetErr::TooManySprites(count, max) => {
+            println!(
+                "Error: found {} images, more than --max-sprites {}",
+                count, max
+            )
+        }
+        SpritesheetErr::TooFewImages(count, min) => {
+            println!(
+                "Error: only {} images left after filtering, --min-images needs {}",
+                count, min
+            )
+        }
+        SpritesheetErr::SpriteTooLarge(name, width, height, max) => {
+            println!(
+                "Error: {} is {}x{}, larger than --max-sprite-dim {}",
+                name, width, height, max
+            )
+        }
+        SpritesheetErr::ImagesDiffer => println!("Error: images differ"),
+        SpritesheetErr::ValidationFailed(count) => {
+            println!("Error: atlas has {} problems", count)
+        }
+        SpritesheetErr::Multiple(errors) => {
+            for error in errors {
+                print_error(error);
+            }
+            println!("Error: {} problems found", errors.len())
+        }
+        SpritesheetErr::SkippedInputs(names) => {
+            println!(
+                "Error: --strict, {} inputs were skipped: {}",
+                names.len(),
+                names.join(", ")
+            )
+        }
+        SpritesheetErr::BatchFailed(failed, total) => {
+            println!("Error: {} of {} jobs failed", failed, total)
+        }
+        SpritesheetErr::MixedColorTypes(message) => {
+            println!("Error: mixed color types, {}", message)
+        }
+        SpritesheetErr::SheetTooLarge(width, height, bytes) => {
+            println!(
+                "Error: can't allocate the {}x{} sheet ({} MiB), pack fewer frames per run or raise --max-alloc",
+                width,
+                height,
+                bytes.div_ceil(1024 * 1024)
+            )
+        }
+    };
+}
+
+// Packs a sheet from another crate: the options are the command-line
+// arguments, and atlas formats can be added at runtime. A writer registered
+// as `engine` is selected with `--format engine` and saved as
+// `<sheet>.engine`; like the built-in text formats it receives the computed
+// layout, with the image and frame names, and returns the file's contents.
+pub struct PackOptions {
+    args: Vec<String>,
+    writers: Vec<atlas::Writer>,
+}
+
+impl PackOptions {
+    pub fn new<S: AsRef<str>>(args: &[S]) -> PackOptions {
+        PackOptions {
+            args: args.iter().map(|arg| arg.as_ref().to_string()).collect(),
+            writers: Vec::new(),
+        }
+    }
+
+    pub fn register_writer(
+        &mut self,
+        name: &str,
+        write: impl Fn(&Layout, &SheetInfo) -> String + Send + Sync + 'static,
+    ) -> &mut PackOptions {
+        self.writers.retain(|writer| writer.name != name);
+        self.writers.push(atlas::Writer {
+            name: name.to_string(),
+            write: std::sync::Arc::new(write),
+        });
+        self
+    }
+
+    pub fn pack(&self) -> Result<(), SpritesheetErr> {
+        pack(&get_settings(&self.args, self.writers.clone())?)?;
+        Ok(())
+    }
+}
+
+fn try_create_spritesheet(args: &[String]) -> Result<(), SpritesheetErr> {
+    let settings = get_settings(args, Vec::new())?;
+    if settings.show_version {
+        print_version();
+        return Ok(());
+    }
+    if settings.watch {
+        return watch::watch(&settings);
+    }
+    pack(&settings)?;
+    Ok(())
+}
+
+// Packs the sheets and returns the paths of the images it wrote.
+fn pack(settings: &Settings) -> Result<Vec<std::path::PathBuf>, SpritesheetErr> {
+    let mut timings = Timings::default();
+    // Inputs dropped along the way, e.g. `f.gif (unknown format)`; with
+    // --strict any of them fails the run before a sheet is written.
+    let mut skipped = Vec::new();
+    let (mut path_to_images, frame_tags) =
+        timings.measure("scanning", || find_sources(settings, &mut skipped))?;
+    if !settings.frame_names.is_empty() {
+        name_frames(&mut path_to_images, &settings.frame_names)?;
+    }
+    if !settings.layers.is_empty() {
+        path_to_images = layers::attach_layers(path_to_images, settings)?;
+    }
+    assign_durations(&mut path_to_images, settings);
+    if settings.sort == Some(SortOrder::ExifTime) {
+        // Page breaks stay where they are, frames move only within a page.
+        for frames in path_to_images.split_mut(|image_info| image_info.page_break) {
+            exif::sort_by_capture_time(frames);
+        }
+    }
+    if settings.analyze {
+        analyze::print_comparison(&path_to_images, settings, &mut skipped);
+        check_skipped(&skipped, settings)?;
+        return Ok(Vec::new());
+    }
+    if let Some(max_sprites) = settings.max_sprites {
+        let count = path_to_images
+            .iter()
+            .filter(|image_info| !image_info.page_break)
+            .count();
+        if count > max_sprites {
+            return Err(SpritesheetErr::TooManySprites(count, max_sprites));
+        }
+    }
+    if settings.patch
+        && (settings.layout_path.is_none()
+            || settings.stream
+            || settings.shrink
+            || !settings.scales.is_empty())
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--patch needs --layout-file and can't be used with --stream, --shrink or --scales"
+                .to_string(),
+        ));
+    }
+    if settings.padding_color.is_some() && settings.padding == (0, 0) {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--padding-color needs --padding".to_string(),
+        ));
+    }
+    if settings.padding_color.is_some() && settings.cell_from_name.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--padding-color can't be used with --cell-from-name".to_string(),
+        ));
+    }
+    if settings.append_path.is_some()
+        && (!matches!(settings.atlas_format, Some(atlas::AtlasFormat::Json))
+            || settings.stream
+            || !settings.scales.is_empty())
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--append needs --format json and can't be used with --stream or --scales".to_string(),
+        ));
+    }
+    if settings.append_path.is_some()
+        && (settings.padding != (0, 0)
+            || settings.stagger
+            || settings.trim
+            || settings.optimize_rows
+            || settings.shrink_last_row
+            || settings.radial
+            || settings.folder_groups
+            || settings.cell_from_name.is_some()
+            || settings.layout_path.is_some()
+            || settings.max_megapixels.is_some()
+            || !settings.orders.is_empty())
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--append keeps the existing grid, it can't be used with --padding, --stagger, --trim, --optimize-rows, --last-row-height auto, --layout radial, --folder-groups, --cell-from-name, --layout-file, --max-megapixels or --order".to_string(),
+        ));
+    }
+    if settings.append_path.is_some() && settings.rename_pattern.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--append matches frames by file name, it can't be used with --rename-pattern"
+                .to_string(),
+        ));
+    }
+    if settings.labels && settings.atlas_format.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--labels makes a contact sheet, it can't be used with --format".to_string(),
+        ));
+    }
+    if settings.final_size.is_some() && settings.fit_dimension.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--final-size and --fit-dimension can't be used together".to_string(),
+        ));
+    }
+    if settings.dither && settings.palette_path.is_none() && settings.match_palette_path.is_none() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--dither needs --palette or --match-palette".to_string(),
+        ));
+    }
+    if settings.palette_path.is_some() && settings.match_palette_path.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--palette and --match-palette can't be used together".to_string(),
+        ));
+    }
+    if settings.changelog && (settings.stream || settings.metadata_only) {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--changelog can't be used with --stream or --metadata-only".to_string(),
+        ));
+    }
+    if settings.crop_to_smallest
+        && (settings.uniform_max || settings.cell_size.is_some() || settings.stream)
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--fit smallest-crop can't be used with --uniform-max, --cell-size or --stream"
+                .to_string(),
+        ));
+    }
+    if !settings.layers.is_empty()
+        && (settings.stream
+            || settings.trim
+            || !settings.rotations.is_empty()
+            || settings.mips > 0
+            || settings.sdf.is_some()
+            || !settings.scales.is_empty()
+            || settings.shrink
+            || settings.patch
+            || settings.reuse_cells
+            || settings.labels
+            || settings.crop_to_smallest
+            || settings.max_sprite_dim.is_some()
+            || settings.max_megapixels.is_some())
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--layers can't be used with options that change sprite pixels or sizes (--stream, --trim, --rotate, --mips, --sdf, --scales, --shrink, --patch, --reuse-cells, --labels, --fit smallest-crop, --max-sprite-dim, --max-megapixels)".to_string(),
+        ));
+    }
+    if !settings.layers.is_empty()
+        && !matches!(
+            settings.atlas_format,
+            None | Some(
+                atlas::AtlasFormat::Json | atlas::AtlasFormat::JsonArray | atlas::AtlasFormat::Toml
+            )
+        )
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--layers needs --format json, json-array or toml".to_string(),
+        ));
+    }
+    if settings.max_megapixels.is_some()
+        && (settings.stream
+            || settings.patch
+            || settings.trim
+            || settings.mips > 0
+            || settings.optimize_rows
+            || settings.shrink_last_row
+            || settings.radial
+            || settings.stagger
+            || !settings.scales.is_empty())
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--max-megapixels can't be used with --stream, --patch, --trim, --mips, --optimize-rows, --last-row-height auto, --layout radial, --stagger or --scales".to_string(),
+        ));
+    }
+    if settings.opaque_threshold.is_some() && !settings.compute_bounds {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--opaque-threshold needs --compute-bounds".to_string(),
+        ));
+    }
+    if settings.rings.is_some() && !settings.radial {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--rings needs --layout radial".to_string(),
+        ));
+    }
+    if settings.radial
+        && (settings.padding != (0, 0)
+            || settings.stagger
+            || settings.serpentine
+            || settings.optimize_rows
+            || settings.shrink_last_row
+            || settings.folder_groups
+            || settings.cell_from_name.is_some()
+            || settings.layout_path.is_some()
+            || !settings.orders.is_empty()
+            || !settings.scales.is_empty()
+            || !matches!(settings.origin, Origin::TopLeft))
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--layout radial can't be used with --padding, --stagger, --serpentine, --optimize-rows, --last-row-height auto, --folder-groups, --cell-from-name, --layout-file, --order, --scales or --origin".to_string(),
+        ));
+    }
+    if settings.watermark_pos.is_some() && settings.watermark_path.is_none() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--watermark-pos needs --watermark".to_string(),
+        ));
+    }
+    if settings.min_size.is_some() && settings.shrink {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--min-size can't be used with --shrink".to_string(),
+        ));
+    }
+    if settings.export_trimmed.is_some() && !settings.trim {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--export-trimmed needs --trim".to_string(),
+        ));
+    }
+    if settings.no_rotation && !settings.rotations.is_empty() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--no-rotation can't be used with --rotate".to_string(),
+        ));
+    }
+    if settings.optimize_rows
+        && (settings.stream
+            || settings.patch
+            || !settings.scales.is_empty()
+            || settings.cell_from_name.is_some()
+            || settings.folder_groups)
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--optimize-rows can't be used with --stream, --patch, --scales, --cell-from-name or --folder-groups"
+                .to_string(),
+        ));
+    }
+    if settings.shrink_last_row
+        && (settings.stream
+            || settings.patch
+            || !settings.scales.is_empty()
+            || settings.cell_from_name.is_some()
+            || settings.folder_groups
+            || settings.optimize_rows
+            || settings.orders.contains(&FillOrder::ColumnMajor))
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--last-row-height auto can't be used with --stream, --patch, --scales, --cell-from-name, --folder-groups, --optimize-rows or --order column-major"
+                .to_string(),
+        ));
+    }
+    if settings.stagger && settings.optimize_rows {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--stagger can't be used with --optimize-rows".to_string(),
+        ));
+    }
+    if settings.folder_groups && settings.cell_from_name.is_some() {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--folder-groups can't be used with --cell-from-name".to_string(),
+        ));
+    }
+    if settings.trim {
+        if settings.stream || !settings.scales.is_empty() || settings.patch {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--trim can't be used with --stream, --scales or --patch".to_string(),
+            ));
+        }
+        if !matches!(
+            settings.atlas_format,
+            None | Some(atlas::AtlasFormat::Json | atlas::AtlasFormat::JsonArray)
+        ) {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--trim only supports the json and json-array atlas formats".to_string(),
+            ));
+        }
+    }
+    if settings.mips > 0 {
+        if settings.stream || settings.trim || !settings.scales.is_empty() {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--mips can't be used with --stream, --trim or --scales".to_string(),
+            ));
+        }
+        if !matches!(
+            settings.atlas_format,
+            None | Some(
+                atlas::AtlasFormat::Json | atlas::AtlasFormat::JsonArray | atlas::AtlasFormat::Toml
+            )
+        ) {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--mips only supports the json, json-array and toml atlas formats".to_string(),
+            ));
+        }
+    }
+    if settings.orders.len() > 1 && (settings.stream || settings.patch || settings.reuse_cells) {
+        return Err(SpritesheetErr::InvalidSettings(
+            "several --order values can't be used with --stream, --patch or --reuse-cells"
+                .to_string(),
+        ));
+    }
+    if settings.orders.contains(&FillOrder::ColumnMajor)
+        && (settings.optimize_rows || settings.cell_from_name.is_some() || settings.folder_groups)
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--order column-major can't be used with --optimize-rows, --cell-from-name or --folder-groups"
+                .to_string(),
+        ));
+    }
+    if settings.reuse_cells {
+        let is_png = output_path(settings)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if settings.layout_path.is_none() || !is_png {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--reuse-cells needs --layout-file and a .png output".to_string(),
+            ));
+        }
+        // The previous sheet only holds the sprites as loaded, so nothing may
+        // change their pixels or size between loading and placement.
+        let transformed = settings.stream
+            || settings.patch
+            || settings.trim
+            || settings.uniform_max
+            || settings.crop_to_smallest
+            || settings.shrink
+            || settings.labels
+            || settings.frame_hash
+            || settings.crop.is_some()
+            || settings.sdf.is_some()
+            || settings.mips > 0
+            || settings.alpha_bleed.is_some()
+            || settings.palette_path.is_some()
+            || settings.match_palette_path.is_some()
+            || settings
+                .color_type
+                .is_some_and(|color_type| color_type != PngColorType::Rgba)
+            || settings.fit_dimension.is_some()
+            || settings.final_size.is_some()
+            || settings.max_sprite_dim.is_some()
+            || !settings.rotations.is_empty()
+            || !settings.scales.is_empty()
+            || !settings.sprite_scales.is_empty()
+            || settings.max_megapixels.is_some();
+        if transformed {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--reuse-cells can't be used with options that change sprite pixels (--trim, --rotate, --labels, --palette, ...)"
+                    .to_string(),
+            ));
+        }
+    }
+    if let Some(color_type) = settings.color_type {
+        let is_png = output_path(settings)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if !is_png {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--color-type needs a .png output".to_string(),
+            ));
+        }
+        if settings.palette_path.is_some() {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--color-type can't be used with --palette".to_string(),
+            ));
+        }
+        if settings.premultiply && !color_type.has_alpha() {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--premultiply needs a --color-type with alpha".to_string(),
+            ));
+        }
+    }
+    if settings.metadata_only {
+        if settings.atlas_format.is_none() {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--metadata-only needs --format".to_string(),
+            ));
+        }
+        // These need the pixels of the finished sheet, or rewrite it.
+        if settings.stream
+            || settings.patch
+            || settings.reuse_cells
+            || settings.shrink
+            || settings.fit_dimension.is_some()
+            || settings.final_size.is_some()
+        {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--metadata-only can't be used with --stream, --patch, --reuse-cells, --shrink, --fit-dimension or --final-size"
+                    .to_string(),
+            ));
+        }
+    }
+    if settings.compute_bounds {
+        if settings.stream || settings.mips > 0 {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--compute-bounds can't be used with --stream or --mips".to_string(),
+            ));
+        }
+        if !matches!(
+            settings.atlas_format,
+            Some(
+                atlas::AtlasFormat::Json | atlas::AtlasFormat::JsonArray | atlas::AtlasFormat::Toml
+            )
+        ) {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--compute-bounds needs --format json, json-array or toml".to_string(),
+            ));
+        }
+    }
+    if !settings.sprite_scales.is_empty() && !settings.uniform_max {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--scale-file needs --uniform-max, scaled sprites no longer share one size".to_string(),
+        ));
+    }
+    if settings.normalized_coords
+        && !matches!(
+            settings.atlas_format,
+            Some(
+                atlas::AtlasFormat::Json | atlas::AtlasFormat::JsonArray | atlas::AtlasFormat::Toml
+            )
+        )
+    {
+        return Err(SpritesheetErr::InvalidSettings(
+            "--coords normalized needs --format json, json-array or toml".to_string(),
+        ));
+    }
+    if settings.stream {
+        if settings.sort.is_some_and(SortOrder::needs_avg_color) {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--sort luminance|hue can't be used with --stream".to_string(),
+            ));
+        }
+        if !settings.scales.is_empty() {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--scales can't be used with --stream".to_string(),
+            ));
+        }
+        if settings.uniform_max {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--uniform-max can't be used with --stream".to_string(),
+            ));
+        }
+        if settings.trim_report
+            || settings.strict_color
+            || settings.sdf.is_some()
+            || !settings.rotations.is_empty()
+        {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--trim-report, --strict-color, --sdf and --rotate can't be used with --stream"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if settings.split_by_tag {
+        if settings.frames_per_page.is_some()
+            || path_to_images
+                .iter()
+                .any(|image_info| image_info.page_break)
+        {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--split-by-tag can't be used with --frames-per-page or page-break marker files"
+                    .to_string(),
+            ));
+        }
+        let mut written = Vec::new();
+        for (path, frames) in split_tags(path_to_images, &output_path(settings)) {
+            written.extend(pack_page(
+                frames,
+                &frame_tags,
+                settings,
+                &path,
+                None,
+                &mut timings,
+                &mut skipped,
+            )?);
+        }
+        finish_pack(&skipped, &timings, settings)?;
+        return Ok(written);
+    }
+    let pages = match settings.frames_per_page {
+        Some(frames_per_page) => {
+            if path_to_images
+                .iter()
+                .any(|image_info| image_info.page_break)
+            {
+                return Err(SpritesheetErr::InvalidSettings(
+                    "--frames-per-page can't be used with page-break marker files".to_string(),
+                ));
+            }
+            chunk_pages(path_to_images, frames_per_page)
+        }
+        None => split_pages(path_to_images),
+    };
+    let page_count = pages.len();
+    let mut written = Vec::new();
+    for (index, page) in pages.into_iter().enumerate() {
+        let path = if page_count == 1 {
+            output_path(settings)
+        } else {
+            page_output_path(&output_path(settings), index)
+        };
+        written.extend(pack_page(
+            page,
+            &frame_tags,
+            settings,
+            &path,
+            (page_count > 1).then_some(index),
+            &mut timings,
+            &mut skipped,
+        )?);
+    }
+    finish_pack(&skipped, &timings, settings)?;
+    Ok(written)
+}
+
+fn finish_pack(
+    skipped: &[String],
+    timings: &Timings,
+    settings: &Settings,
+) -> Result<(), SpritesheetErr> {
+    if settings.json_log {
+        // Free-text output only mentions skipped inputs in aggregate.
+        for entry in skipped.iter() {
+            let (name, reason) = entry.rsplit_once(" (").unwrap_or((entry, ")"));
+            warn(settings, format!("skipped ({}", reason), Some(name));
+        }
+    }
+    if settings.show_timings {
+        timings.print();
+    }
+    Ok(())
+}
+
+// Groups the frames by animation name for --split-by-tag, each group saved
+// next to the output as `walk.png`, `idle.png`, ... in order of its first
+// frame. Frames without a name stay in the output itself.
+fn split_tags(
+    images_data: Vec<ImageData>,
+    path: &std::path::Path,
+) -> Vec<(std::path::PathBuf, Vec<ImageData>)> {
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let mut sheets: Vec<(std::path::PathBuf, Vec<ImageData>)> = Vec::new();
+    for image_info in images_data {
+        let sheet_path = match atlas::animation_name(&image_info.name) {
+            // Folder names from --folder-groups or several inputs can't be
+            // part of a file name.
+            Some(name) => path.with_file_name(format!("{}.{}", name.replace('/', "_"), extension)),
+            None => path.to_path_buf(),
+        };
+        match sheets.iter_mut().find(|(path, _)| *path == sheet_path) {
+            Some((_, frames)) => frames.push(image_info),
+            None => sheets.push((sheet_path, vec![image_info])),
+        }
+    }
+    sheets
+}
+
+// Prints a warning, or with --log-format json writes it to stderr as one JSON
+// object per line: `{"level": "warning", "message": ..., "path": ...}`, the
+// path only when the warning is about one input.
+fn warn(settings: &Settings, message: String, path: Option<&str>) {
+    if !settings.json_log {
+        println!("Warning: {}", message);
+        return;
+    }
+    let path = path.map_or(String::new(), |path| {
+        format!(", \"path\": {}", atlas::json_string(path))
+    });
+    eprintln!(
+        "{{\"level\": \"warning\", \"message\": {}{}}}",
+        atlas::json_string(&message),
+        path
+    );
+}
+
+// Every `frames_per_page` images start a new page (--frames-per-page).
+fn chunk_pages(mut images_data: Vec<ImageData>, frames_per_page: usize) -> Vec<Vec<ImageData>> {
+    let mut pages = Vec::new();
+    while images_data.len() > frames_per_page {
+        let rest = images_data.split_off(frames_per_page);
+        pages.push(std::mem::replace(&mut images_data, rest));
+    }
+    pages.push(images_data);
+    pages
+}
+
+// Splits the frames at every page-break marker. Markers at the start or end,
+// or next to each other, don't produce empty pages.
+fn split_pages(images_data: Vec<ImageData>) -> Vec<Vec<ImageData>> {
+    let mut pages = vec![Vec::new()];
+    for image_info in images_data {
+        if !image_info.page_break {
+            pages.last_mut().unwrap().push(image_info);
+        } else if !pages.last().unwrap().is_empty() {
+            pages.push(Vec::new());
+        }
+    }
+    if pages.len() > 1 && pages.last().unwrap().is_empty() {
+        pages.pop();
+    }
+    pages
+}
+
+// `spritesheet.png` becomes `spritesheet_0.png`, `spritesheet_1.png`, ...
+fn page_output_path(path: &std::path::Path, page: usize) -> std::path::PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{}.{}", stem, page, extension))
+}
+
+// Gives the frames the names from --names, in scan order, before anything
+// reads or sorts by them.
+fn name_frames(images_data: &mut [ImageData], names: &[String]) -> Result<(), SpritesheetErr> {
+    let mut frames: Vec<&mut ImageData> = images_data
+        .iter_mut()
+        .filter(|image_info| !image_info.page_break)
+        .collect();
+    if frames.len() != names.len() {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "--names lists {} names for {} frames",
+            names.len(),
+            frames.len()
+        )));
+    }
+    for (image_info, name) in frames.iter_mut().zip(names) {
+        image_info.name = name.clone();
+    }
+    Ok(())
+}
+
+// Fills in the durations the sources don't give: a `_100ms` suffix on the
+// frame name with --name-durations, otherwise 1000 / --fps milliseconds.
+fn assign_durations(images_data: &mut [ImageData], settings: &Settings) {
+    let default = settings.fps.map(|fps| (1000.0 / fps).round() as u32);
+    for image_info in images_data
+        .iter_mut()
+        .filter(|image_info| !image_info.page_break && image_info.duration.is_none())
+    {
+        let from_name = match settings.name_durations {
+            true => name_duration(&image_info.name),
+            false => None,
+        };
+        image_info.duration = from_name.or(default);
+    }
+}
+
+// The N of a name ending in `_Nms`, before the extension.
+fn name_duration(name: &str) -> Option<u32> {
+    let stem = std::path::Path::new(name).file_stem()?.to_str()?;
+    let (_, suffix) = stem.rsplit_once('_')?;
+    suffix.strip_suffix("ms")?.parse().ok()
+}
+
+// Loads, lays out and saves the frames of one output sheet, returning the
+// paths of the images written for it.
+fn pack_page(
+    path_to_images: Vec<ImageData>,
+    frame_tags: &[aseprite::FrameTag],
+    settings: &Settings,
+    path: &std::path::Path,
+    page: Option<usize>,
+    timings: &mut Timings,
+    skipped: &mut Vec<String>,
+) -> Result<Vec<std::path::PathBuf>, SpritesheetErr> {
+    if settings.stream {
+        let cell = settings.cell_size.ok_or_else(|| {
+            SpritesheetErr::InvalidSettings("--stream requires --cell-size".to_string())
+        })?;
+        let path_to_images = filter_image_paths(path_to_images, cell, settings, skipped)?;
+        check_skipped(skipped, settings)?;
+        let count = path_to_images
+            .iter()
+            .filter(|image_info| !image_info.placeholder)
+            .count();
+        check_min_images(count, settings)?;
+        let names: Vec<&str> = path_to_images
+            .iter()
+            .map(|image_info| image_info.name.as_str())
+            .collect();
+        let cell = rounded_cell(cell, settings);
+        let mut layout = layout_for(settings, &names, (cell.0, cell.1 + label_height(settings)))?;
+        apply_fill_order(&mut layout, fill_orders(settings)[0], settings);
+        layout.page = page;
+        apply_stagger(&mut layout, settings);
+        let layout = final_layout(&layout, settings);
+        let (spritesheet, images) = timings.measure("loading and packing", || {
+            stream_spritesheet(&layout, path_to_images, settings)
+        })?;
+        if settings.report_memory {
+            // Only one decoded frame is held at a time.
+            let frame_bytes = cell.0 as u64 * cell.1 as u64 * 4 * canvas_channel_bytes(settings);
+            report_memory(&layout, frame_bytes, path, settings);
+        }
+        return save_outputs(
+            spritesheet,
+            &layout,
+            &images,
+            frame_tags,
+            settings,
+            path,
+            timings,
+        );
+    }
+
+    // How many images --preflight sized, counting those it already dropped.
+    let (path_to_images, preflight_total) = if settings.preflight {
+        let (images, total) =
+            timings.measure("preflight", || preflight(path_to_images, settings, skipped))?;
+        (images, Some(total))
+    } else {
+        (path_to_images, None)
+    };
+    let stamps = settings
+        .reuse_cells
+        .then(|| reuse::source_stamps(&path_to_images));
+    let mut images = timings.measure("loading", || match settings.reuse_cells {
+        true => reuse::load_reusing_cells(path_to_images, path, settings),
+        false => collect_images(path_to_images, settings),
+    })?;
+    if let Some(max_dimension) = settings.max_sprite_dim {
+        limit_sprite_dimensions(&mut images, max_dimension, settings)?;
+    }
+    if settings.strict_color {
+        check_color_types(&images)?;
+    }
+    if let Some(sort) = settings.sort.filter(|sort| sort.needs_avg_color()) {
+        sort_images(&mut images, sort);
+        if !settings.compute_avg_color {
+            images.iter_mut().for_each(|sprite| sprite.avg_color = None);
+        }
+    }
+    let (images, cell) = if settings.uniform_max {
+        let images = drop_excluded_resolutions(images, settings);
+        let width = images.iter().map(|sprite| sprite.image.width()).max();
+        let height = images.iter().map(|sprite| sprite.image.height()).max();
+        (images, (width.unwrap_or(0), height.unwrap_or(0)))
+    } else if settings.crop_to_smallest {
+        crop_to_smallest(drop_excluded_resolutions(images, settings))
+    } else {
+        let images = filter_images(images, preflight_total, settings, skipped)?;
+        let first = images.iter().find(|sprite| !sprite.placeholder).unwrap();
+        let cell = (first.image.width(), first.image.height());
+        (images, cell)
+    };
+    let (images, cell) = if settings.rotations.is_empty() {
+        (images, cell)
+    } else {
+        rotate_sprites(images, settings)
+    };
+    if settings.trim_report {
+        trim::print_report(&images);
+    }
+    let (mut images, cell) = if settings.trim {
+        trim::trim_sprites(images)
+    } else {
+        (images, cell)
+    };
+    if let Some(dir) = &settings.export_trimmed {
+        trim::export_sprites(&images, dir)?;
+    }
+    if let Some(radius) = settings.sdf {
+        for sprite in images.iter_mut().filter(|sprite| !sprite.placeholder) {
+            sprite.image = sdf::distance_field(&sprite.image, radius).into();
+        }
+    }
+    let (mut images, cell) = if settings.mips > 0 {
+        mips::add_mips(images, settings.mips, settings)
+    } else {
+        (images, cell)
+    };
+    if settings.optimize_rows {
+        images.sort_by_key(|sprite| std::cmp::Reverse(sprite.image.height()));
+    }
+    check_skipped(skipped, settings)?;
+    check_min_images(
+        images.iter().filter(|sprite| !sprite.placeholder).count(),
+        settings,
+    )?;
+    let cell = rounded_cell(cell, settings);
+    let (grid, images) = match &settings.append_path {
+        Some(sheet_path) => append::append_frames(images, sheet_path, settings.origin)?,
+        None => {
+            let names: Vec<&str> = images.iter().map(|sprite| sprite.name.as_str()).collect();
+            let cell = (cell.0, cell.1 + label_height(settings));
+            (layout_for(settings, &names, cell)?, images)
+        }
+    };
+    let mut written = Vec::new();
+    for &order in fill_orders(settings) {
+        let mut layout = grid.clone();
+        apply_fill_order(&mut layout, order, settings);
+        layout.page = page;
+        apply_stagger(&mut layout, settings);
+        let path = &order_output_path(path, order, settings);
+        if settings.optimize_rows {
+            fit_rows(
+                &mut layout,
+                &cell_heights(&images, settings),
+                settings.origin,
+            );
+        }
+        if settings.shrink_last_row {
+            fit_last_row(&mut layout, &cell_heights(&images, settings));
+        }
+        if settings.changelog {
+            patch::print_changelog(&images, patch::read_cache(path)?.as_deref(), path);
+        }
+        if settings.patch {
+            if let Some(cache) = patch::read_cache(path)? {
+                let layout = final_layout(&layout, settings);
+                written.extend(patch::save_patch(&layout, images, &cache, path, settings)?);
+                return Ok(written);
+            }
+        }
+        if settings.scales.is_empty() {
+            let fitted = match settings.max_megapixels {
+                Some(megapixels) => fit_megapixels(&layout, &images, megapixels, settings)?,
+                None => None,
+            };
+            let (layout, images) = match &fitted {
+                Some((layout, images)) => (layout.clone(), images.as_slice()),
+                None => (final_layout(&layout, settings), images.as_slice()),
+            };
+            if settings.metadata_only {
+                save_metadata(&layout, images, frame_tags, settings, path)?;
+                continue;
+            }
+            let spritesheet =
+                timings.measure("packing", || create_spritesheet(&layout, images, settings))?;
+            report_partial_cells(&layout, images, settings);
+            if settings.report_memory {
+                report_memory(&layout, frames_bytes(images), path, settings);
+            }
+            written.extend(save_outputs(
+                spritesheet,
+                &layout,
+                images,
+                frame_tags,
+                settings,
+                path,
+                timings,
+            )?);
+            if let Some(stamps) = &stamps {
+                reuse::write_cells(&layout, images, stamps, path)?;
+            }
+        }
+        for &scale in settings.scales.iter() {
+            let (layout, images) = scale_sprites(&layout, &images, scale, settings);
+            let layout = final_layout(&layout, settings);
+            if settings.metadata_only {
+                let path = &scaled_output_path(path, scale);
+                save_metadata(&layout, &images, frame_tags, settings, path)?;
+                continue;
+            }
+            let spritesheet =
+                timings.measure("packing", || create_spritesheet(&layout, &images, settings))?;
+            report_partial_cells(&layout, &images, settings);
+            let path = &scaled_output_path(path, scale);
+            if settings.report_memory {
+                report_memory(&layout, frames_bytes(&images), path, settings);
+            }
+            written.extend(save_outputs(
+                spritesheet,
+                &layout,
+                &images,
+                frame_tags,
+                settings,
+                path,
+                timings,
+            )?);
+        }
+        if settings.patch || settings.changelog {
+            patch::write_cache(&images, path)?;
+        }
+    }
+    Ok(written)
+}
+
+fn frames_bytes(images: &[Sprite]) -> u64 {
+    images
+        .iter()
+        .map(|sprite| sprite.image.as_bytes().len() as u64)
+        .sum()
+}
+
+// Estimates the memory a sheet took from the decoded frames held while
+// packing and the canvas allocation; encoder buffers aren't counted.
+fn report_memory(layout: &Layout, frames_bytes: u64, path: &std::path::Path, settings: &Settings) {
+    let canvas_bytes =
+        layout.width as u64 * layout.height as u64 * 4 * canvas_channel_bytes(settings);
+    let size = |bytes: u64| match bytes < 1024 * 1024 {
+        true => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        false => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+    };
+    println!(
+        "Memory for {}: frames {} + canvas {} = {} peak (estimate)",
+        path.display(),
+        size(frames_bytes),
+        size(canvas_bytes),
+        size(frames_bytes + canvas_bytes)
+    );
+}
+
+// In the plain grid every sprite should fill its cell exactly; warns about
+// each frame that doesn't, since it would be misaligned against the grid. The
+// modes that size cells to the largest sprite are skipped.
+fn report_partial_cells(layout: &Layout, images: &[Sprite], settings: &Settings) {
+    if settings.uniform_max
+        || settings.trim
+        || settings.mips > 0
+        || !settings.rotations.is_empty()
+        || settings.cell_multiple.is_some()
+    {
+        return;
+    }
+    for (index, sprite) in images.iter().enumerate().take(layout.positions.len()) {
+        let (width, height) = layout.frame_size(index);
+        let height = height - label_height(settings);
+        if !sprite.placeholder && sprite.image.dimensions() != (width, height) {
+            let message = format!(
+                "frame {} ({}) is {}x{} but its cell is {}x{}",
+                index,
+                sprite.name,
+                sprite.image.width(),
+                sprite.image.height(),
+                width,
+                height
+            );
+            warn(settings, message, Some(&sprite.name));
+        }
+    }
+}
+
+fn fill_orders(settings: &Settings) -> &[FillOrder] {
+    match settings.orders.is_empty() {
+        true => &[FillOrder::RowMajor],
+        false => &settings.orders,
+    }
+}
+
+// Refills the grid column by column (top to bottom, then left to right) for
+// --order column-major. The grid itself, and so the sheet size, is unchanged.
+fn apply_fill_order(layout: &mut Layout, order: FillOrder, settings: &Settings) {
+    if order == FillOrder::RowMajor {
+        return;
+    }
+    let rows = layout.rows.max(1);
+    layout.positions = (0..layout.positions.len() as u32)
+        .map(|index| {
+            let column = index / rows;
+            let row = match settings.serpentine && column % 2 == 1 {
+                true => rows - 1 - index % rows,
+                false => index % rows,
+            };
+            (column * layout.cell_width, row * layout.cell_height)
+        })
+        .collect();
+    apply_origin(layout, settings.origin);
+}
+
+// With several --order values each one gets its own `_rowmajor` or
+// `_colmajor` sheet, otherwise the name is kept.
+fn order_output_path(
+    path: &std::path::Path,
+    order: FillOrder,
+    settings: &Settings,
+) -> std::path::PathBuf {
+    if settings.orders.len() < 2 {
+        return path.to_path_buf();
+    }
+    let suffix = match order {
+        FillOrder::RowMajor => "rowmajor",
+        FillOrder::ColumnMajor => "colmajor",
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{}.{}", stem, suffix, extension))
+}
+
+fn check_skipped(skipped: &[String], settings: &Settings) -> Result<(), SpritesheetErr> {
+    if settings.strict && !skipped.is_empty() {
+        return Err(SpritesheetErr::SkippedInputs(skipped.to_vec()));
+    }
+    Ok(())
+}
+
+// Fails when fewer frames than --min-images are left after filtering, which
+// usually means the wrong folder or filters were given.
+fn check_min_images(count: usize, settings: &Settings) -> Result<(), SpritesheetErr> {
+    match settings.min_images {
+        Some(min_images) if count < min_images => {
+            Err(SpritesheetErr::TooFewImages(count, min_images))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Rotates every sprite by the last --rotate whose pattern matches its name
+// (a rule without pattern matches all) and returns the cell that fits the
+// largest rotated bounding box.
+fn rotate_sprites(mut images: Vec<Sprite>, settings: &Settings) -> (Vec<Sprite>, (u32, u32)) {
+    let mut cell = (0, 0);
+    for sprite in images.iter_mut().filter(|sprite| !sprite.placeholder) {
+        let rule = settings.rotations.iter().rev().find(|(pattern, _)| {
+            pattern
+                .as_ref()
+                .is_none_or(|pattern| ignore::matches(pattern, &sprite.name))
+        });
+        if let Some(&(_, degrees)) = rule {
+            sprite.image = rotate::rotate(&sprite.image, degrees, settings.pixel_art);
+            sprite.angle = degrees;
+        }
+        cell = (
+            cell.0.max(sprite.image.width()),
+            cell.1.max(sprite.image.height()),
+        );
+    }
+    (images, cell)
+}
+
+// Downscales every sprite by the same factor, on the same grid, so the
+// finished sheet has at most `megapixels` million pixels, or None if it
+// already fits. Padding doesn't shrink with the sprites, so the factor is
+// lowered a little until the sheet fits.
+fn fit_megapixels(
+    layout: &Layout,
+    images: &[Sprite],
+    megapixels: f64,
+    settings: &Settings,
+) -> Result<Option<(Layout, Vec<Sprite>)>, SpritesheetErr> {
+    let budget = megapixels * 1_000_000.0;
+    let area = |layout: &Layout| layout.width as f64 * layout.height as f64;
+    let full = final_layout(layout, settings);
+    if area(&full) <= budget {
+        return Ok(None);
+    }
+    let mut scale = (budget / area(&full)).sqrt() as f32;
+    loop {
+        let (scaled, scaled_images) = scale_sprites(layout, images, scale, settings);
+        let mut scaled = final_layout(&scaled, settings);
+        if area(&scaled) <= budget {
+            println!(
+                "Scaled sprites by {:.4} to fit {} megapixels ({}x{})",
+                scale, megapixels, scaled.width, scaled.height
+            );
+            scaled.sprite_scale = Some(scale);
+            return Ok(Some((scaled, scaled_images)));
+        }
+        if scaled.cell_width <= 1 && scaled.cell_height <= 1 {
+            return Err(SpritesheetErr::InvalidSettings(format!(
+                "the sheet can't be scaled down to {} megapixels",
+                megapixels
+            )));
+        }
+        scale *= 0.99;
+    }
+}
+
+// Resizes every sprite by the scale and lays them out on the same grid, so
+// frame positions only differ from the original sheet by that factor.
+fn scale_sprites(
+    layout: &Layout,
+    images: &[Sprite],
+    scale: f32,
+    settings: &Settings,
+) -> (Layout, Vec<Sprite>) {
+    let scale_size = |width: u32, height: u32| -> (u32, u32) {
+        (
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+        )
+    };
+    let cell = scale_size(layout.cell_width, layout.cell_height);
+    let scaled_layout = Layout {
+        cell_width: cell.0,
+        cell_height: cell.1,
+        width: layout.columns * cell.0,
+        height: layout.rows * cell.1,
+        positions: layout
+            .positions
+            .iter()
+            .map(|position| {
+                (
+                    position.0 / layout.cell_width * cell.0,
+                    position.1 / layout.cell_height * cell.1,
+                )
+            })
+            .collect(),
+        ..layout.clone()
+    };
+
+    let filter = resize_filter(settings);
+    let scaled_images = images
+        .iter()
+        .map(|sprite| Sprite {
+            name: sprite.name.clone(),
+            image: {
+                let (width, height) = scale_size(sprite.image.width(), sprite.image.height());
+                if sprite.placeholder
+                    || (width, height) == (sprite.image.width(), sprite.image.height())
+                {
+                    sprite.image.clone()
+                } else {
+                    sprite.image.resize_exact(width, height, filter)
+                }
+            },
+            hash: sprite.hash,
+            avg_color: sprite.avg_color,
+            duration: sprite.duration,
+            placeholder: sprite.placeholder,
+            trim: sprite.trim,
+            angle: sprite.angle,
+            mips: sprite.mips.clone(),
+            layers: Vec::new(),
+        })
+        .collect();
+    (scaled_layout, scaled_images)
+}
+
+fn scaled_output_path(path: &std::path::Path, scale: f32) -> std::path::PathBuf {
+    if scale == 1.0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = format!("{}@{}x", stem, scale);
+    if let Some(extension) = path.extension() {
+        file_name = format!("{}.{}", file_name, extension.to_string_lossy());
+    }
+    path.with_file_name(file_name)
+}
+
+fn find_sources(
+    settings: &Settings,
+    skipped: &mut Vec<String>,
+) -> Result<(Vec<ImageData>, Vec<aseprite::FrameTag>), SpritesheetErr> {
+    if let Some(path) = &settings.manifest_path {
+        return Ok((manifest::read_frames(path, settings)?, Vec::new()));
+    }
+    match (&settings.aseprite_path, &settings.zip_path) {
+        (Some(path), _) => {
+            let import = aseprite::read_frames(path)?;
+            Ok((import.images, import.frame_tags))
+        }
+        (None, Some(path)) => Ok((zip::read_images(path, settings.sort_group)?, Vec::new())),
+        (None, None) => Ok((find_images_path(settings, skipped)?, Vec::new())),
+    }
+}
+
+#[derive(Default)]
+struct Timings {
+    steps: Vec<(&'static str, std::time::Duration)>,
+}
+
+impl Timings {
+    fn measure<T>(&mut self, step: &'static str, function: impl FnOnce() -> T) -> T {
+        let start = std::time::Instant::now();
+        let result = function();
+        self.steps.push((step, start.elapsed()));
+        result
+    }
+
+    fn print(&self) {
+        for (step, duration) in self.steps.iter() {
+            println!("{}: {:.3}s", step, duration.as_secs_f64());
+        }
+    }
+}
+
+// Fills `{cols}`, `{rows}`, `{cw}` and `{ch}` in the output file name from
+// the layout, e.g. `atlas_{cols}x{rows}.png` -> `atlas_8x4.png`.
+fn expand_output_template(path: &std::path::Path, layout: &Layout) -> std::path::PathBuf {
+    let Some(file_name) = path.file_name().map(|name| name.to_string_lossy()) else {
+        return path.to_path_buf();
+    };
+    if !file_name.contains('{') {
+        return path.to_path_buf();
+    }
+    let file_name = file_name
+        .replace("{cols}", &layout.columns.to_string())
+        .replace("{rows}", &layout.rows.to_string())
+        .replace("{cw}", &layout.cell_width.to_string())
+        .replace("{ch}", &layout.cell_height.to_string());
+    path.with_file_name(file_name)
+}
+
+// Saves the sheet with its extra images and atlas, returning the paths of
+// the images written: the sheet, its other --layers sheets, the alpha mask
+// and the thumbnail.
+fn save_outputs(
+    mut spritesheet: DynamicImage,
+    layout: &Layout,
+    images: &[Sprite],
+    frame_tags: &[aseprite::FrameTag],
+    settings: &Settings,
+    path: &std::path::Path,
+    timings: &mut Timings,
+) -> Result<Vec<std::path::PathBuf>, SpritesheetErr> {
+    let path = &expand_output_template(path, layout);
+    let mut layout = layout.clone();
+    if settings.shrink {
+        (spritesheet, layout) = shrink_sheet(spritesheet, &layout);
+    }
+    // The other --layers sheets, packed the same way and finished like the
+    // first one so they stay aligned with the atlas.
+    let mut layer_sheets = Vec::new();
+    for (index, layer) in settings.layers.iter().skip(1).enumerate() {
+        let sprites = layers::layer_sprites(images, index);
+        let sheet = create_spritesheet(&layout, &sprites, settings)?;
+        let mut sheet = finish_sheet(sheet, &mut layout.clone(), settings)?;
+        if let Some(color_type) = settings.color_type {
+            sheet = convert_color_type(sheet, color_type, settings);
+        }
+        layer_sheets.push((layers::layer_path(path, layer), sheet));
+    }
+    spritesheet = finish_sheet(spritesheet, &mut layout, settings)?;
+    let layout = &layout;
+    let mut written = Vec::new();
+    if settings.alpha_mask {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mask_path = path.with_file_name(format!("{}_alpha.png", stem));
+        alpha_mask(&spritesheet).save(&mask_path)?;
+        written.push(mask_path);
+    }
+    if let Some((width, height)) = settings.thumbnail {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let thumbnail_path = path.with_file_name(format!("{}_thumb.png", stem));
+        // Only ever shrinks, a sheet that already fits is saved as it is.
+        let thumbnail = if spritesheet.width() > width || spritesheet.height() > height {
+            spritesheet.resize(width, height, resize_filter(settings))
+        } else {
+            spritesheet.clone()
+        };
+        thumbnail.save(&thumbnail_path)?;
+        written.push(thumbnail_path);
+    }
+    if let Some(color_type) = settings.color_type {
+        spritesheet = convert_color_type(spritesheet, color_type, settings);
+    }
+    let metadata = settings.embed_metadata.then(|| {
+        let image_name = path.file_name().unwrap_or_default().to_string_lossy();
+        atlas::write_json(layout, images, frame_tags, &image_name, false, settings)
+    });
+    let sheet_path = match settings.layers.first() {
+        Some(layer) => layers::layer_path(path, layer),
+        None => path.clone(),
+    };
+    timings.measure("encoding", || {
+        save_image(spritesheet, &sheet_path, metadata, settings)?;
+        written.push(sheet_path);
+        layer_sheets
+            .into_iter()
+            .try_for_each(|(layer_path, sheet)| {
+                save_image(sheet, &layer_path, None, settings)?;
+                written.push(layer_path);
+                Ok::<(), SpritesheetErr>(())
+            })
+    })?;
+    if let Some(format) = settings.atlas_format {
+        atlas::save_atlas(format, layout, images, frame_tags, path, settings)?;
+    }
+    Ok(written)
+}
+
+// The pixel work done on a packed sheet before it's saved, which can also
+// scale the layout's coordinates.
+fn finish_sheet(
+    mut spritesheet: DynamicImage,
+    layout: &mut Layout,
+    settings: &Settings,
+) -> Result<DynamicImage, SpritesheetErr> {
+    if let Some(depth) = settings.alpha_bleed {
+        match &mut spritesheet {
+            DynamicImage::ImageRgba16(buffer) => postprocess::bleed_alpha(buffer, depth),
+            DynamicImage::ImageRgba8(buffer) => postprocess::bleed_alpha(buffer, depth),
+            _ => (),
+        }
+    }
+    if settings.sdf.is_some() {
+        spritesheet = DynamicImage::ImageLuma8(spritesheet.to_luma8());
+    }
+    if let Some(max_dimension) = settings.fit_dimension {
+        let scale;
+        (spritesheet, scale) = fit_sheet(spritesheet, max_dimension, settings);
+        layout.scale = (scale, scale);
+    }
+    if let Some((width, height)) = settings.final_size {
+        let scale = (
+            width as f32 / spritesheet.width() as f32,
+            height as f32 / spritesheet.height() as f32,
+        );
+        if (scale.0 - scale.1).abs() > 0.001 {
+            let message = format!(
+                "--final-size {}x{} stretches the {}x{} sheet unevenly ({}x horizontally, {}x vertically)",
+                width,
+                height,
+                spritesheet.width(),
+                spritesheet.height(),
+                scale.0,
+                scale.1
+            );
+            warn(settings, message, None);
+        }
+        spritesheet = spritesheet.resize_exact(width, height, resize_filter(settings));
+        layout.scale = scale;
+    }
+    if let Some(logo_path) = &settings.watermark_path {
+        add_watermark(&mut spritesheet, logo_path, settings)?;
+    }
+    if let Some(reference) = &settings.match_palette_path {
+        let palette = palette::read_image_palette(reference)?;
+        let mut buffer = spritesheet.to_rgba8();
+        palette::remap(&mut buffer, &palette, settings.dither)?;
+        spritesheet = buffer.into();
+    }
+    // Last, so resizing above still works on straight alpha.
+    if settings.premultiply && settings.assume_alpha != Some(AlphaMode::Premultiplied) {
+        match &mut spritesheet {
+            DynamicImage::ImageRgba16(buffer) => postprocess::premultiply_alpha(buffer),
+            DynamicImage::ImageRgba8(buffer) => postprocess::premultiply_alpha(buffer),
+            _ => (),
+        }
+    }
+    Ok(spritesheet)
+}
+
+// Blends the --watermark image over the finished sheet in the --watermark-pos
+// corner (bottom right by default), at its own size. It covers whatever cells
+// lie there and isn't listed in the atlas.
+fn add_watermark(
+    spritesheet: &mut DynamicImage,
+    logo_path: &std::path::Path,
+    settings: &Settings,
+) -> Result<(), SpritesheetErr> {
+    let logo = image::open(logo_path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", logo_path.display())))?;
+    let right = spritesheet.width() as i64 - logo.width() as i64;
+    let bottom = spritesheet.height() as i64 - logo.height() as i64;
+    let (x, y) = match settings.watermark_pos.unwrap_or(Origin::BottomRight) {
+        Origin::TopLeft => (0, 0),
+        Origin::TopRight => (right, 0),
+        Origin::BottomLeft => (0, bottom),
+        Origin::BottomRight => (right, bottom),
+    };
+    image::imageops::overlay(spritesheet, &logo, x, y);
+    Ok(())
+}
+
+// Converts the sheet to the PNG color type asked for with --color-type,
+// keeping its bit depth. Dropping alpha composites the sheet over the
+// --background color, or black.
+fn convert_color_type(
+    spritesheet: DynamicImage,
+    color_type: PngColorType,
+    settings: &Settings,
+) -> DynamicImage {
+    let sixteen_bit = matches!(
+        spritesheet,
+        DynamicImage::ImageRgba16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+    );
+    let spritesheet = match color_type.has_alpha() {
+        true => spritesheet,
+        false => {
+            let background = settings.background.unwrap_or(image::Rgba([0, 0, 0, 0]));
+            let background = [background[0], background[1], background[2]];
+            match sixteen_bit {
+                true => {
+                    let mut buffer = spritesheet.to_rgba16();
+                    postprocess::flatten_alpha(&mut buffer, background);
+                    buffer.into()
+                }
+                false => {
+                    let mut buffer = spritesheet.to_rgba8();
+                    postprocess::flatten_alpha(&mut buffer, background);
+                    buffer.into()
+                }
+            }
+        }
+    };
+    match (color_type, sixteen_bit) {
+        (PngColorType::Rgba, false) => spritesheet.to_rgba8().into(),
+        (PngColorType::Rgba, true) => spritesheet.to_rgba16().into(),
+        (PngColorType::Rgb, false) => spritesheet.to_rgb8().into(),
+        (PngColorType::Rgb, true) => spritesheet.to_rgb16().into(),
+        (PngColorType::Gray, false) => spritesheet.to_luma8().into(),
+        (PngColorType::Gray, true) => spritesheet.to_luma16().into(),
+        (PngColorType::GrayAlpha, false) => spritesheet.to_luma_alpha8().into(),
+        (PngColorType::GrayAlpha, true) => spritesheet.to_luma_alpha16().into(),
+    }
+}
+
+// Writes only the atlas (--metadata-only), named as if the sheet had been
+// saved to `path`. No canvas is allocated.
+fn save_metadata(
+    layout: &Layout,
+    images: &[Sprite],
+    frame_tags: &[aseprite::FrameTag],
+    settings: &Settings,
+    path: &std::path::Path,
+) -> Result<(), SpritesheetErr> {
+    let path = &expand_output_template(path, layout);
+    let format = settings.atlas_format.unwrap();
+    atlas::save_atlas(format, layout, images, frame_tags, path, settings)
+}
+
+// Downscales the whole sheet so neither side exceeds `max_dimension`. The
+// atlas keeps the unscaled coordinates and records the factor, so UVs
+// normalized by the atlas size still match the smaller texture.
+fn fit_sheet(
+    spritesheet: DynamicImage,
+    max_dimension: u32,
+    settings: &Settings,
+) -> (DynamicImage, f32) {
+    let (width, height) = spritesheet.dimensions();
+    if width.max(height) <= max_dimension {
+        return (spritesheet, 1.0);
+    }
+    let scale = max_dimension as f32 / width.max(height) as f32;
+    let fitted_width = ((width as f32 * scale).round() as u32).clamp(1, max_dimension);
+    let fitted_height = ((height as f32 * scale).round() as u32).clamp(1, max_dimension);
+    let filter = resize_filter(settings);
+    (
+        spritesheet.resize_exact(fitted_width, fitted_height, filter),
+        scale,
+    )
+}
+
+// Crops the sheet to its non-transparent area, widened so that no frame that
+// has visible pixels gets cut, and moves the frame positions by the offset.
+// Fully transparent frames left outside the crop are marked as cropped, with
+// an empty rect at the origin.
+fn shrink_sheet(spritesheet: DynamicImage, layout: &Layout) -> (DynamicImage, Layout) {
+    let Some((x, y, width, height)) = trim::opaque_bounds(&spritesheet) else {
+        return (spritesheet, layout.clone());
+    };
+    let (mut left, mut top, mut right, mut bottom) = (x, y, x + width, y + height);
+    loop {
+        let bounds = (left, top, right, bottom);
+        for (index, position) in layout.positions.iter().enumerate() {
+            let cell_right = position.0 + layout.cell_width;
+            let cell_bottom = position.1 + layout.frame_size(index).1;
+            if position.0 < right && cell_right > left && position.1 < bottom && cell_bottom > top {
+                left = left.min(position.0);
+                top = top.min(position.1);
+                right = right.max(cell_right);
+                bottom = bottom.max(cell_bottom);
+            }
+        }
+        if bounds == (left, top, right, bottom) {
+            break;
+        }
+    }
+
+    let mut shrunk_layout = layout.clone();
+    shrunk_layout.width = right - left;
+    shrunk_layout.height = bottom - top;
+    shrunk_layout.cropped_frames = vec![false; layout.positions.len()];
+    for (index, position) in shrunk_layout.positions.iter_mut().enumerate() {
+        let (width, height) = layout.frame_size(index);
+        let inside = position.0 >= left
+            && position.1 >= top
+            && position.0 + width <= right
+            && position.1 + height <= bottom;
+        match inside {
+            true => *position = (position.0 - left, position.1 - top),
+            false => {
+                *position = (0, 0);
+                shrunk_layout.cropped_frames[index] = true;
+            }
+        }
+    }
+    let spritesheet = spritesheet.crop_imm(left, top, right - left, bottom - top);
+    (spritesheet, shrunk_layout)
+}
+
+// Grayscale copy of the sheet's alpha channel, same size and layout.
+fn alpha_mask(spritesheet: &DynamicImage) -> DynamicImage {
+    match spritesheet {
+        DynamicImage::ImageRgba16(buffer) => {
+            image::ImageBuffer::from_fn(buffer.width(), buffer.height(), |x, y| {
+                image::Luma([buffer.get_pixel(x, y)[3]])
+            })
+            .into()
+        }
+        _ => {
+            let buffer = spritesheet.to_rgba8();
+            image::GrayImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+                image::Luma([buffer.get_pixel(x, y)[3]])
+            })
+            .into()
+        }
+    }
+}
+
+// Grid positions for the frames, by --cell-from-name when it's given.
+fn layout_for(
+    settings: &Settings,
+    names: &[&str],
+    cell: (u32, u32),
+) -> Result<Layout, SpritesheetErr> {
+    match &settings.cell_from_name {
+        Some(pattern) => named_cells::layout(pattern, names, cell),
+        None if settings.folder_groups => Ok(groups::layout(names, cell)),
+        None if settings.radial => Ok(radial::layout(
+            names.len(),
+            cell,
+            settings.rings.unwrap_or(1),
+        )),
+        None => plan_layout(settings, names.len(), cell),
+    }
+}
+
+fn plan_layout(
+    settings: &Settings,
+    images_count: usize,
+    cell: (u32, u32),
+) -> Result<Layout, SpritesheetErr> {
+    let stored_layout = match &settings.layout_path {
+        Some(path) => read_layout_file(path)?,
+        None => None,
+    };
+
+    let mut layout = match stored_layout {
+        Some((columns, rows)) => {
+            let mut layout = calculate_layout(columns, images_count, cell, settings.serpentine);
+            if layout.rows > rows {
+                let message = format!(
+                    "{} images don't fit the stored {}x{} layout, adding rows",
+                    images_count, columns, rows
+                );
+                warn(settings, message, None);
+            }
+            layout.rows = layout.rows.max(rows);
+            layout.height = layout.rows * cell.1;
+            layout
+        }
+        None => {
+            let row_count = choose_row_count(settings, images_count, cell)?;
+            let layout = calculate_layout(row_count, images_count, cell, settings.serpentine);
+            if let Some(path) = &settings.layout_path {
+                write_layout_file(path, &layout)?;
+            }
+            layout
+        }
+    };
+    apply_origin(&mut layout, settings.origin);
+    Ok(layout)
+}
+
+// The layout a sheet is created from: padded, then grown to --min-size.
+fn final_layout(layout: &Layout, settings: &Settings) -> Layout {
+    let mut layout = padded_layout(layout, settings);
+    if let Some(min_size) = settings.min_size {
+        grow_to_min_size(&mut layout, min_size, settings.origin);
+    }
+    layout
+}
+
+// Widens and heightens the sheet to at least `min_size` with empty space. The
+// grid stays in the --origin corner, so the space goes to the right and
+// bottom unless the origin is on that side.
+fn grow_to_min_size(layout: &mut Layout, min_size: (u32, u32), origin: Origin) {
+    let extra = (
+        min_size.0.saturating_sub(layout.width),
+        min_size.1.saturating_sub(layout.height),
+    );
+    let shift = match origin {
+        Origin::TopLeft => (0, 0),
+        Origin::TopRight => (extra.0, 0),
+        Origin::BottomLeft => (0, extra.1),
+        Origin::BottomRight => extra,
+    };
+    for position in layout.positions.iter_mut() {
+        *position = (position.0 + shift.0, position.1 + shift.1);
+    }
+    layout.width += extra.0;
+    layout.height += extra.1;
+}
+
+// Spreads the cells apart by --padding pixels. Only the gaps between cells
+// are padded, unless --outer-padding also adds the padding around the sheet.
+// Applied last, to a layout whose cells still touch.
+fn padded_layout(layout: &Layout, settings: &Settings) -> Layout {
+    let mut layout = layout.clone();
+    let padding = settings.padding;
+    if padding == (0, 0) {
+        return layout;
+    }
+    let outer = if settings.outer_padding {
+        padding
+    } else {
+        (0, 0)
+    };
+
+    // Rows shortened by --optimize-rows don't start at multiples of the cell.
+    let mut row_tops: Vec<u32> = layout.positions.iter().map(|position| position.1).collect();
+    row_tops.sort_unstable();
+    row_tops.dedup();
+    let row_of = |top: u32| match layout.frame_heights.is_empty() {
+        true => top / layout.cell_height.max(1),
+        false => row_tops.binary_search(&top).unwrap() as u32,
+    };
+    let positions = layout
+        .positions
+        .iter()
+        .map(|&(x, y)| {
+            let column = x / layout.cell_width.max(1);
+            (
+                x + outer.0 + column * padding.0,
+                y + outer.1 + row_of(y) * padding.1,
+            )
+        })
+        .collect();
+    let rows = match layout.frame_heights.is_empty() {
+        true => layout.rows,
+        false => row_tops.len() as u32,
+    };
+
+    layout.positions = positions;
+    layout.width += layout.columns.saturating_sub(1) * padding.0 + 2 * outer.0;
+    layout.height += rows.saturating_sub(1) * padding.1 + 2 * outer.1;
+    layout
+}
+
+// Shortens every row to its tallest frame. The frames are sorted tallest first
+// for --optimize-rows, so each row holds sprites of similar height. Columns
+// stay as they are, rows are stacked again from the top, or from the bottom
+// when the origin is a bottom corner.
+fn fit_rows(layout: &mut Layout, heights: &[u32], origin: Origin) {
+    let columns = layout.columns as usize;
+    let row_heights: Vec<u32> = heights
+        .chunks(columns)
+        .map(|row| row.iter().copied().max().unwrap_or(0))
+        .collect();
+    let total_height: u32 = row_heights.iter().sum();
+
+    let mut top = 0;
+    for (row, &row_height) in row_heights.iter().enumerate() {
+        let frames = row * columns..((row + 1) * columns).min(layout.positions.len());
+        for index in frames {
+            layout.positions[index].1 = match origin {
+                Origin::BottomLeft | Origin::BottomRight => total_height - top - row_height,
+                Origin::TopLeft | Origin::TopRight => top,
+            };
+        }
+        top += row_height;
+    }
+    layout.frame_heights = (0..layout.positions.len())
+        .map(|index| row_heights[index / columns])
+        .collect();
+    layout.height = total_height;
+}
+
+// Shortens a partially filled last row to its tallest frame for
+// --last-row-height auto, leaving the other rows a full cell tall. With a
+// bottom origin the last row is the top one, so the rows below it move up.
+fn fit_last_row(layout: &mut Layout, heights: &[u32]) {
+    let Some(&(_, last_top)) = layout.positions.last() else {
+        return;
+    };
+    let in_last_row = |index: usize| layout.positions[index].1 == last_top;
+    let last_row: Vec<usize> = (0..layout.positions.len())
+        .filter(|&index| in_last_row(index))
+        .collect();
+    if last_row.len() >= layout.columns as usize {
+        return;
+    }
+    let row_height = last_row
+        .iter()
+        .map(|&index| heights[index])
+        .max()
+        .unwrap_or(0);
+    let shrink = layout.cell_height.saturating_sub(row_height);
+    if shrink == 0 {
+        return;
+    }
+    layout.frame_heights = (0..layout.positions.len())
+        .map(|index| match in_last_row(index) {
+            true => row_height,
+            false => layout.cell_height,
+        })
+        .collect();
+    for position in layout.positions.iter_mut() {
+        if position.1 > last_top {
+            position.1 -= shrink;
+        }
+    }
+    layout.height -= shrink;
+}
+
+// The cell height every sprite needs, before --optimize-rows or
+// --last-row-height auto shorten rows to them.
+fn cell_heights(images: &[Sprite], settings: &Settings) -> Vec<u32> {
+    images
+        .iter()
+        .map(|sprite| rounded_cell((0, sprite.image.height()), settings).1 + label_height(settings))
+        .collect()
+}
+
+// Mirrors the top-left based positions so frame 0 sits in the chosen corner
+// and filling proceeds away from it.
+fn apply_origin(layout: &mut Layout, origin: Origin) {
+    let (flip_x, flip_y) = match origin {
+        Origin::TopLeft => (false, false),
+        Origin::TopRight => (true, false),
+        Origin::BottomLeft => (false, true),
+        Origin::BottomRight => (true, true),
+    };
+    let last_x = (layout.columns - 1) * layout.cell_width;
+    let last_y = layout.rows.saturating_sub(1) * layout.cell_height;
+    for position in layout.positions.iter_mut() {
+        if flip_x {
+            position.0 = last_x - position.0;
+        }
+        if flip_y {
+            position.1 = last_y - position.1;
+        }
+    }
+}
+
+// Shifts every odd row (counted from the top of the sheet) right by half a
+// cell for hex tilesets (--stagger); the sheet grows by that half cell.
+fn apply_stagger(layout: &mut Layout, settings: &Settings) {
+    if !settings.stagger {
+        return;
+    }
+    let offset = stagger_offset(layout);
+    let cell_height = layout.cell_height.max(1);
+    for position in layout.positions.iter_mut() {
+        if (position.1 / cell_height) % 2 == 1 {
+            position.0 += offset;
+        }
+    }
+    if layout.rows > 1 {
+        layout.width += offset;
+    }
+}
+
+fn stagger_offset(layout: &Layout) -> u32 {
+    layout.cell_width / 2
+}
+
+fn read_layout_file(path: &std::path::Path) -> Result<Option<(u32, u32)>, SpritesheetErr> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let error = || SpritesheetErr::LayoutFileError(path.display().to_string());
+    let contents = std::fs::read_to_string(path).map_err(|_| error())?;
+    let layout = json::parse(&contents).ok_or_else(error)?;
+    let columns = layout.get("columns").and_then(|value| value.as_u32());
+    let rows = layout.get("rows").and_then(|value| value.as_u32());
+    match (columns, rows) {
+        (Some(columns), Some(rows)) if columns > 0 => Ok(Some((columns, rows))),
+        _ => Err(error()),
+    }
+}
+
+// Reads `pattern factor` lines (`boss_*.png 0.5`); the last line whose glob
+// matches a file name sets its scale.
+fn read_scale_file(path: &std::path::Path) -> Result<Vec<(String, f32)>, SpritesheetErr> {
+    let error = || SpritesheetErr::ImportError(format!("can't read {}", path.display()));
+    let contents = std::fs::read_to_string(path).map_err(|_| error())?;
+    let mut scales = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (pattern, scale) = line
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| SpritesheetErr::InvalidArgument(line.to_string()))?;
+        let scale = scale
+            .parse::<f32>()
+            .ok()
+            .filter(|scale| scale.is_finite() && *scale > 0.0)
+            .ok_or_else(|| SpritesheetErr::InvalidArgument(line.to_string()))?;
+        scales.push((pattern.trim().to_string(), scale));
+    }
+    Ok(scales)
+}
+
+fn write_layout_file(path: &std::path::Path, layout: &Layout) -> Result<(), SpritesheetErr> {
+    let contents = format!(
+        "{{\n  \"columns\": {},\n  \"rows\": {},\n  \"cellWidth\": {},\n  \"cellHeight\": {}\n}}\n",
+        layout.columns, layout.rows, layout.cell_width, layout.cell_height
+    );
+    std::fs::write(path, contents)
+        .map_err(|_| SpritesheetErr::LayoutFileError(path.display().to_string()))
+}
+
+fn choose_row_count(
+    settings: &Settings,
+    images_count: usize,
+    cell: (u32, u32),
+) -> Result<u32, SpritesheetErr> {
+    if let Some(aspect) = settings.aspect {
+        Ok(calculate_aspect_row_count(images_count, cell, aspect))
+    } else if settings.optimize_fit {
+        Ok(calculate_fit_column_count(images_count, cell))
+    } else if settings.square {
+        Ok(calculate_square_column_count(images_count))
+    } else if settings.use_auto_row_count {
+        Ok(calculate_row_count(images_count))
+    } else {
+        println!("Image count: {}", images_count);
+        Ok(get_input_row_count()?)
+    }
+}
+
+fn get_input_row_count() -> Result<u32, ParseIntError> {
+    print!("Enter row count: ");
+    _ = std::io::stdout().flush();
+    let mut input_string: String = String::from("");
+    std::io::stdin().read_line(&mut input_string).unwrap();
+    input_string.trim().parse()
+}
+
+#[derive(Default)]
+struct Settings {
+    use_auto_row_count: bool,
+    atlas_format: Option<atlas::AtlasFormat>,
+    // Atlas writers added through PackOptions::register_writer.
+    writers: Vec<atlas::Writer>,
+    compute_avg_color: bool,
+    aseprite_path: Option<std::path::PathBuf>,
+    cell_size: Option<(u32, u32)>,
+    stream: bool,
+    output_path: Option<std::path::PathBuf>,
+    near_lossless: Option<u8>,
+    layout_path: Option<std::path::PathBuf>,
+    append_path: Option<std::path::PathBuf>,
+    alpha_bleed: Option<u32>,
+    max_sprites: Option<usize>,
+    zip_path: Option<std::path::PathBuf>,
+    input_dirs: Vec<std::path::PathBuf>,
+    sort: Option<SortOrder>,
+    show_version: bool,
+    keep: KeepResolution,
+    show_timings: bool,
+    crop: Option<(u32, u32, u32, u32)>,
+    background: Option<image::Rgba<u8>>,
+    padding: (u32, u32),
+    padding_color: Option<image::Rgba<u8>>,
+    aspect: Option<(u32, u32)>,
+    bit_depth: Option<u8>,
+    scales: Vec<f32>,
+    filter: Option<image::imageops::FilterType>,
+    output_in_input: bool,
+    watch: bool,
+    uniform_max: bool,
+    align: Align,
+    labels: bool,
+    serpentine: bool,
+    embed_metadata: bool,
+    manifest_path: Option<std::path::PathBuf>,
+    trim_report: bool,
+    jobs: Option<usize>,
+    animations: bool,
+    shrink: bool,
+    alpha_mask: bool,
+    palette_path: Option<std::path::PathBuf>,
+    strict_color: bool,
+    origin: Origin,
+    fit_dimension: Option<u32>,
+    patch: bool,
+    max_alloc: Option<u64>,
+    trim: bool,
+    rename_pattern: Option<String>,
+    preflight: bool,
+    sdf: Option<u32>,
+    cell_from_name: Option<String>,
+    max_sprite_dim: Option<u32>,
+    downscale_oversized: bool,
+    pixel_art: bool,
+    thumbnail: Option<(u32, u32)>,
+    rotations: Vec<(Option<String>, f32)>,
+    optimize_rows: bool,
+    dither: bool,
+    excluded_resolutions: Vec<(u32, u32)>,
+    pivot: Option<(f32, f32)>,
+    frame_hash: bool,
+    final_size: Option<(u32, u32)>,
+    outer_padding: bool,
+    square: bool,
+    normalized_coords: bool,
+    no_overwrite: bool,
+    sprite_scales: Vec<(String, f32)>,
+    mips: u32,
+    strict: bool,
+    deterministic: bool,
+    frames_per_page: Option<usize>,
+    compute_bounds: bool,
+    fail_fast: bool,
+    optimize_fit: bool,
+    reuse_cells: bool,
+    orders: Vec<FillOrder>,
+    frame_names: Vec<String>,
+    analyze: bool,
+    premultiply: bool,
+    report_memory: bool,
+    cell_multiple: Option<u32>,
+    color_key: Option<[u8; 3]>,
+    atlas_by_position: bool,
+    folder_groups: bool,
+    json_log: bool,
+    metadata_only: bool,
+    assume_alpha: Option<AlphaMode>,
+    stagger: bool,
+    match_palette_path: Option<std::path::PathBuf>,
+    no_rotation: bool,
+    shrink_last_row: bool,
+    export_trimmed: Option<std::path::PathBuf>,
+    color_type: Option<PngColorType>,
+    min_size: Option<(u32, u32)>,
+    sort_group: Option<usize>,
+    watermark_path: Option<std::path::PathBuf>,
+    watermark_pos: Option<Origin>,
+    split_by_tag: bool,
+    crop_to_smallest: bool,
+    min_images: Option<usize>,
+    changelog: bool,
+    radial: bool,
+    rings: Option<u32>,
+    opaque_threshold: Option<u8>,
+    fps: Option<f32>,
+    name_durations: bool,
+    layers: Vec<String>,
+    max_megapixels: Option<f64>,
+}
+
+#[derive(Clone, Copy, Default)]
+enum KeepResolution {
+    #[default]
+    MostCommon,
+    Largest,
+    Smallest,
+}
+
+#[derive(Clone, Copy, Default)]
+enum Align {
+    #[default]
+    TopLeft,
+    Center,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FillOrder {
+    RowMajor,
+    ColumnMajor,
+}
+
+#[derive(Clone, Copy, Default)]
+enum Origin {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AlphaMode {
+    Straight,
+    Premultiplied,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PngColorType {
+    Rgba,
+    Rgb,
+    Gray,
+    GrayAlpha,
+}
+
+impl PngColorType {
+    fn has_alpha(self) -> bool {
+        matches!(self, PngColorType::Rgba | PngColorType::GrayAlpha)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortOrder {
+    Luminance,
+    Hue,
+    // Ordered before loading, from the files rather than the pixels.
+    ExifTime,
+}
+
+impl SortOrder {
+    fn needs_avg_color(self) -> bool {
+        self != SortOrder::ExifTime
+    }
+}
+
+fn get_settings(args: &[String], writers: Vec<atlas::Writer>) -> Result<Settings, SpritesheetErr> {
+    let mut settings = Settings {
+        writers,
+        ..Settings::default()
+    };
+    let mut args = args.iter().cloned();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "auto" => settings.use_auto_row_count = true,
+            "--version" => settings.show_version = true,
+            "--format" => {
+                let value = next_value(&mut args, &arg)?;
+                let format = atlas::AtlasFormat::parse(&value)
+                    .or_else(|| {
+                        let mut writers = settings.writers.iter();
+                        let index = writers.position(|writer| writer.name == value);
+                        index.map(atlas::AtlasFormat::Custom)
+                    })
+                    .ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.atlas_format = Some(format);
+            }
+            "--avg-color" => settings.compute_avg_color = true,
+            "--aseprite" => settings.aseprite_path = Some(next_value(&mut args, &arg)?.into()),
+            "--cell-size" => {
+                let value = next_value(&mut args, &arg)?;
+                let size = parse_size(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.cell_size = Some(size);
+            }
+            "--stream" => settings.stream = true,
+            "--output" => settings.output_path = Some(next_value(&mut args, &arg)?.into()),
+            "--layout-file" => settings.layout_path = Some(next_value(&mut args, &arg)?.into()),
+            "--append" => settings.append_path = Some(next_value(&mut args, &arg)?.into()),
+            "--alpha-bleed" => settings.alpha_bleed = Some(next_value(&mut args, &arg)?.parse()?),
+            "--max-sprites" => settings.max_sprites = Some(next_value(&mut args, &arg)?.parse()?),
+            "--min-images" => settings.min_images = Some(next_value(&mut args, &arg)?.parse()?),
+            "--zip" => settings.zip_path = Some(next_value(&mut args, &arg)?.into()),
+            "--input" => settings
+                .input_dirs
+                .push(next_value(&mut args, &arg)?.into()),
+            "--sort" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.sort = Some(match value.as_str() {
+                    "luminance" => SortOrder::Luminance,
+                    "hue" => SortOrder::Hue,
+                    "exif-time" => SortOrder::ExifTime,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                });
+            }
+            "--keep" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.keep = match value.as_str() {
+                    "most-common" => KeepResolution::MostCommon,
+                    "largest" => KeepResolution::Largest,
+                    "smallest" => KeepResolution::Smallest,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--timings" => settings.show_timings = true,
+            "--crop" => {
+                let value = next_value(&mut args, &arg)?;
+                let crop: Vec<u32> = value
+                    .split(',')
+                    .map(|part| part.trim().parse())
+                    .collect::<Result<_, _>>()?;
+                match crop[..] {
+                    [x, y, width, height] if width > 0 && height > 0 => {
+                        settings.crop = Some((x, y, width, height))
+                    }
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                }
+            }
+            "--background" => {
+                let value = next_value(&mut args, &arg)?;
+                let color = parse_color(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.background = Some(color);
+            }
+            "--padding" => {
+                // One value for both axes, or horizontal x vertical.
+                let value = next_value(&mut args, &arg)?;
+                settings.padding = match value.split_once('x') {
+                    Some((x, y)) => (x.trim().parse()?, y.trim().parse()?),
+                    None => (value.parse()?, value.parse()?),
+                };
+            }
+            "--padding-color" => {
+                let value = next_value(&mut args, &arg)?;
+                let color = parse_color(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.padding_color = Some(color);
+            }
+            "--aspect" => {
+                let value = next_value(&mut args, &arg)?;
+                let aspect = value.split_once(':').and_then(|(width, height)| {
+                    let aspect = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+                    (aspect.0 > 0 && aspect.1 > 0).then_some(aspect)
+                });
+                settings.aspect = Some(aspect.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--bit-depth" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.bit_depth = match value.as_str() {
+                    "8" => Some(8),
+                    "16" => Some(16),
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--scales" => {
+                let value = next_value(&mut args, &arg)?;
+                let scales: Option<Vec<f32>> = value
+                    .split(',')
+                    .map(|part| part.trim().parse().ok().filter(|scale| *scale > 0.0))
+                    .collect();
+                settings.scales = scales.ok_or(SpritesheetErr::InvalidArgument(value))?;
+            }
+            "--filter" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.filter = Some(match value.as_str() {
+                    "nearest" => image::imageops::FilterType::Nearest,
+                    "triangle" => image::imageops::FilterType::Triangle,
+                    "catmull-rom" => image::imageops::FilterType::CatmullRom,
+                    "gaussian" => image::imageops::FilterType::Gaussian,
+                    "lanczos3" => image::imageops::FilterType::Lanczos3,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                });
+            }
+            "--output-in-input" => settings.output_in_input = true,
+            "--watch" => settings.watch = true,
+            "--uniform-max" => settings.uniform_max = true,
+            "--labels" => settings.labels = true,
+            "--serpentine" => settings.serpentine = true,
+            "--embed-metadata" => settings.embed_metadata = true,
+            "--jobs" => {
+                let value = next_value(&mut args, &arg)?;
+                let jobs = value.parse().ok().filter(|jobs| *jobs > 0);
+                settings.jobs = Some(jobs.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--patch" => settings.patch = true,
+            "--max-alloc" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.max_alloc =
+                    Some(parse_bytes(&value).ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--fit-dimension" => {
+                let value = next_value(&mut args, &arg)?;
+                let dimension = value.parse().ok().filter(|dimension| *dimension > 0);
+                settings.fit_dimension =
+                    Some(dimension.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--origin" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.origin =
+                    parse_corner(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+            }
+            "--strict-color" => settings.strict_color = true,
+            "--palette" => settings.palette_path = Some(next_value(&mut args, &arg)?.into()),
+            "--alpha-mask" => settings.alpha_mask = true,
+            "--shrink" => settings.shrink = true,
+            "--animations" => settings.animations = true,
+            "--trim-report" => settings.trim_report = true,
+            "--trim" => settings.trim = true,
+            "--preflight" => settings.preflight = true,
+            "--max-sprite-dim" => {
+                let value = next_value(&mut args, &arg)?;
+                let dimension = value.parse().ok().filter(|dimension| *dimension > 0);
+                settings.max_sprite_dim =
+                    Some(dimension.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--pixel-art" => settings.pixel_art = true,
+            "--optimize-rows" => settings.optimize_rows = true,
+            "--frame-hash" => settings.frame_hash = true,
+            "--outer-padding" => settings.outer_padding = true,
+            "--square" => settings.square = true,
+            "--no-overwrite" => settings.no_overwrite = true,
+            "--strict" => settings.strict = true,
+            "--deterministic" => settings.deterministic = true,
+            "--compute-bounds" => settings.compute_bounds = true,
+            "--fail-fast" => settings.fail_fast = true,
+            "--optimize-fit" => settings.optimize_fit = true,
+            "--reuse-cells" => settings.reuse_cells = true,
+            "--order" => {
+                let value = next_value(&mut args, &arg)?;
+                for order in value.split(',') {
+                    let order = match order.trim() {
+                        "row-major" => FillOrder::RowMajor,
+                        "column-major" => FillOrder::ColumnMajor,
+                        _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                    };
+                    if !settings.orders.contains(&order) {
+                        settings.orders.push(order);
+                    }
+                }
+            }
+            "--analyze" => settings.analyze = true,
+            "--premultiply" => settings.premultiply = true,
+            "--report-memory" => settings.report_memory = true,
+            "--folder-groups" => settings.folder_groups = true,
+            "--metadata-only" => settings.metadata_only = true,
+            "--stagger" => settings.stagger = true,
+            "--no-rotation" => settings.no_rotation = true,
+            "--export-trimmed" => {
+                settings.export_trimmed = Some(next_value(&mut args, &arg)?.into())
+            }
+            "--match-palette" => {
+                settings.match_palette_path = Some(next_value(&mut args, &arg)?.into())
+            }
+            "--split-by-tag" => settings.split_by_tag = true,
+            "--changelog" => settings.changelog = true,
+            "--layout" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.radial = match value.as_str() {
+                    "radial" => true,
+                    "grid" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--opaque-threshold" => {
+                let value = next_value(&mut args, &arg)?;
+                let threshold = value.parse().ok().filter(|&threshold| threshold > 0);
+                settings.opaque_threshold =
+                    Some(threshold.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--fps" => {
+                let value = next_value(&mut args, &arg)?;
+                let fps = value.parse().ok().filter(|&fps: &f32| fps > 0.0);
+                settings.fps = Some(fps.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--name-durations" => settings.name_durations = true,
+            "--layers" => {
+                let value = next_value(&mut args, &arg)?;
+                let layers: Vec<String> = value.split(',').map(str::to_string).collect();
+                let valid = layers.len() > 1
+                    && layers.iter().enumerate().all(|(index, layer)| {
+                        !layer.is_empty() && !layers[..index].contains(layer)
+                    });
+                if !valid {
+                    return Err(SpritesheetErr::InvalidArgument(value));
+                }
+                settings.layers = layers;
+            }
+            "--max-megapixels" => {
+                let value = next_value(&mut args, &arg)?;
+                let megapixels = value
+                    .parse()
+                    .ok()
+                    .filter(|&megapixels: &f64| megapixels > 0.0);
+                settings.max_megapixels =
+                    Some(megapixels.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--rings" => settings.rings = Some(next_value(&mut args, &arg)?.parse()?),
+            "--fit" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.crop_to_smallest = match value.as_str() {
+                    "smallest-crop" => true,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--watermark" => settings.watermark_path = Some(next_value(&mut args, &arg)?.into()),
+            "--watermark-pos" => {
+                let value = next_value(&mut args, &arg)?;
+                let corner = parse_corner(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.watermark_pos = Some(corner);
+            }
+            "--sort-by-group" => {
+                let value = next_value(&mut args, &arg)?;
+                let group = value.parse().ok().filter(|&group: &usize| group > 0);
+                settings.sort_group = Some(group.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--min-size" => {
+                let value = next_value(&mut args, &arg)?;
+                let size = parse_size(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.min_size = Some(size);
+            }
+            "--color-type" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.color_type = Some(match value.as_str() {
+                    "rgba" => PngColorType::Rgba,
+                    "rgb" => PngColorType::Rgb,
+                    "gray" => PngColorType::Gray,
+                    "gray-alpha" => PngColorType::GrayAlpha,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                });
+            }
+            "--assume-alpha" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.assume_alpha = Some(match value.as_str() {
+                    "straight" => AlphaMode::Straight,
+                    "premultiplied" => AlphaMode::Premultiplied,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                });
+            }
+            "--log-format" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.json_log = match value.as_str() {
+                    "json" => true,
+                    "text" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--atlas-order" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.atlas_by_position = match value.as_str() {
+                    "position" => true,
+                    "input" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--last-row-height" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.shrink_last_row = match value.as_str() {
+                    "auto" => true,
+                    "fixed" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--color-key" => {
+                let value = next_value(&mut args, &arg)?;
+                let color = parse_color(&value)
+                    .filter(|_| value.strip_prefix('#').unwrap_or(&value).len() == 6);
+                let color = color.ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.color_key = Some([color[0], color[1], color[2]]);
+            }
+            "--cell-multiple" => {
+                let value = next_value(&mut args, &arg)?;
+                match value.parse()? {
+                    0 => return Err(SpritesheetErr::InvalidArgument(value)),
+                    multiple => settings.cell_multiple = Some(multiple),
+                }
+            }
+            "--names" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.frame_names = value.split(',').map(|name| name.to_string()).collect();
+            }
+            "--frames-per-page" => {
+                let value = next_value(&mut args, &arg)?;
+                let count = value.parse().ok().filter(|count| *count > 0);
+                settings.frames_per_page =
+                    Some(count.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--mips" => settings.mips = next_value(&mut args, &arg)?.parse()?,
+            "--scale-file" => {
+                let path = next_value(&mut args, &arg)?;
+                settings.sprite_scales = read_scale_file(std::path::Path::new(&path))?;
+            }
+            "--coords" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.normalized_coords = match value.as_str() {
+                    "normalized" => true,
+                    "pixel" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--final-size" => {
+                let value = next_value(&mut args, &arg)?;
+                let size = parse_size(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.final_size = Some(size);
+            }
+            "--pivot" => {
+                let value = next_value(&mut args, &arg)?;
+                let pivot: Vec<f32> = value
+                    .split(',')
+                    .map(|part| part.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| SpritesheetErr::InvalidArgument(value.clone()))?;
+                match pivot[..] {
+                    [x, y] if x.is_finite() && y.is_finite() => settings.pivot = Some((x, y)),
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                }
+            }
+            "--exclude-resolution" => {
+                let value = next_value(&mut args, &arg)?;
+                let size = parse_size(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.excluded_resolutions.push(size);
+            }
+            "--dither" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.dither = match value.as_str() {
+                    "floyd-steinberg" => true,
+                    "none" => false,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--rotate" => {
+                let value = next_value(&mut args, &arg)?;
+                let (pattern, degrees) = match value.rsplit_once('=') {
+                    Some((pattern, degrees)) => (Some(pattern.to_string()), degrees),
+                    None => (None, value.as_str()),
+                };
+                let degrees = degrees
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|degrees| degrees.is_finite());
+                let degrees =
+                    degrees.ok_or_else(|| SpritesheetErr::InvalidArgument(value.clone()))?;
+                settings.rotations.push((pattern, degrees));
+            }
+            "--thumbnail" => {
+                let value = next_value(&mut args, &arg)?;
+                let size = parse_size(&value).ok_or(SpritesheetErr::InvalidArgument(value))?;
+                settings.thumbnail = Some(size);
+            }
+            "--oversized" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.downscale_oversized = match value.as_str() {
+                    "reject" => false,
+                    "downscale" => true,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--cell-from-name" => {
+                let pattern = next_value(&mut args, &arg)?;
+                if !named_cells::is_valid_pattern(&pattern) {
+                    return Err(SpritesheetErr::InvalidArgument(pattern));
+                }
+                settings.cell_from_name = Some(pattern);
+            }
+            "--sdf" => {
+                let value = next_value(&mut args, &arg)?;
+                let radius = value.parse().ok().filter(|radius| *radius > 0);
+                settings.sdf = Some(radius.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            "--rename-pattern" => {
+                let pattern = next_value(&mut args, &arg)?;
+                let rest = ["{name}", "{stem}", "{index}"]
+                    .iter()
+                    .fold(pattern.clone(), |rest, placeholder| {
+                        rest.replace(placeholder, "")
+                    });
+                if rest.contains(['{', '}']) {
+                    return Err(SpritesheetErr::InvalidArgument(pattern));
+                }
+                settings.rename_pattern = Some(pattern);
+            }
+            "--manifest" => settings.manifest_path = Some(next_value(&mut args, &arg)?.into()),
+            "--align" => {
+                let value = next_value(&mut args, &arg)?;
+                settings.align = match value.as_str() {
+                    "top-left" => Align::TopLeft,
+                    "center" => Align::Center,
+                    _ => return Err(SpritesheetErr::InvalidArgument(value)),
+                };
+            }
+            "--lossless" => settings.near_lossless = None,
+            "--near-lossless" => {
+                let value = next_value(&mut args, &arg)?;
+                let level = value.parse().ok().filter(|level| *level <= 100);
+                settings.near_lossless = Some(level.ok_or(SpritesheetErr::InvalidArgument(value))?);
+            }
+            _ => return Err(SpritesheetErr::InvalidArgument(arg)),
+        }
+    }
+
+    Ok(settings)
+}
+
+fn print_version() {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    println!("Input formats: png, jpeg, bmp, webp, tiff (from folders, Aseprite JSON or ZIP)");
+    println!("Output formats: png, webp (lossless or near-lossless)");
+    println!("Atlas formats: {}", atlas::AtlasFormat::NAMES.join(", "));
+}
+
+fn next_value(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<String, SpritesheetErr> {
+    args.next()
+        .ok_or_else(|| SpritesheetErr::InvalidArgument(flag.to_string()))
+}
+
+fn parse_corner(str: &str) -> Option<Origin> {
+    match str {
+        "top-left" => Some(Origin::TopLeft),
+        "top-right" => Some(Origin::TopRight),
+        "bottom-left" => Some(Origin::BottomLeft),
+        "bottom-right" => Some(Origin::BottomRight),
+        _ => None,
+    }
+}
+
+fn parse_size(str: &str) -> Option<(u32, u32)> {
+    let (width, height) = str.split_once('x')?;
+    let size = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    if size.0 == 0 || size.1 == 0 {
+        return None;
+    }
+    Some(size)
+}
+
+// Byte count with an optional K, M or G (binary) suffix, e.g. `512M`.
+fn parse_bytes(str: &str) -> Option<u64> {
+    let str = str.trim();
+    let (number, multiplier) = match str.char_indices().last()? {
+        (index, 'K' | 'k') => (&str[..index], 1 << 10),
+        (index, 'M' | 'm') => (&str[..index], 1 << 20),
+        (index, 'G' | 'g') => (&str[..index], 1 << 30),
+        _ => (str, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+fn parse_color(str: &str) -> Option<image::Rgba<u8>> {
+    let hex = str.strip_prefix('#').unwrap_or(str);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let mut color = image::Rgba([0, 0, 0, 255]);
+    for (channel, index) in (0..hex.len()).step_by(2).enumerate() {
+        color[channel] = u8::from_str_radix(&hex[index..index + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
+fn find_images_path(
+    settings: &Settings,
+    skipped: &mut Vec<String>,
+) -> Result<Vec<ImageData>, SpritesheetErr> {
+    let mut images: Vec<ImageData> = Vec::new();
+
+    let input_dirs = if settings.input_dirs.is_empty() {
+        vec![std::env::current_dir().expect("Can't find current dir")]
+    } else {
+        settings.input_dirs.clone()
+    };
+
+    for input_dir in input_dirs.iter() {
+        for (path, relative_name) in
+            list_input_files(input_dir, settings.folder_groups, settings.sort_group)?
+        {
+            let file_name = path.file_name().unwrap_or_default();
+            if file_name.to_string_lossy().contains(PAGE_BREAK_MARKER) {
+                images.push(ImageData {
+                    page_break: true,
+                    ..ImageData::placeholder(file_name.to_string_lossy().into_owned())
+                });
+                continue;
+            }
+            let extension: Vec<&str> = file_name.to_str().unwrap().split('.').collect();
+            let format = extension
+                .get(1)
+                .and_then(|extension| get_image_format(extension))
+                .or_else(|| sniff_format(&path));
+
+            if let Some(format) = format {
+                let mut name = relative_name;
+                if input_dirs.len() > 1 {
+                    name = format!("{}/{}", input_dir.display(), name);
+                    if settings.deterministic {
+                        name = name.replace('\\', "/");
+                    }
+                }
+                images.push(ImageData {
+                    name,
+                    path,
+                    format,
+                    duration: None,
+                    bytes: None,
+                    placeholder: false,
+                    page_break: false,
+                    layers: Vec::new(),
+                });
+            } else {
+                skipped.push(format!("{} (unknown format)", relative_name));
+            }
+        }
+    }
+
+    if !images.is_empty() {
+        Ok(images)
+    } else {
+        Err(SpritesheetErr::NoImagesFound)
+    }
+}
+
+// The files of an input directory in natural order, with their names. With
+// --folder-groups the files of each subdirectory follow in its place, named
+// `folder/file`. Entries matching the directory's .packerignore are left out.
+// --sort-by-group orders by the given number group first.
+fn list_input_files(
+    dir: &std::path::Path,
+    folder_groups: bool,
+    sort_group: Option<usize>,
+) -> Result<Vec<(std::path::PathBuf, String)>, SpritesheetErr> {
+    let entries = std::fs::read_dir(dir).map_err(|_| {
+        SpritesheetErr::InvalidSettings(format!("can't read dir {}", dir.display()))
+    })?;
+    let ignore_patterns = ignore::read_patterns(dir);
+
+    let mut entries: Vec<_> = entries
+        .flatten()
+        .filter(|entry| {
+            let metadata = entry.metadata().expect("Access to file denied");
+            metadata.is_file() || (folder_groups && metadata.is_dir())
+        })
+        .filter(|entry| !ignore::is_ignored(&ignore_patterns, &entry.file_name().to_string_lossy()))
+        .collect();
+    entries.sort_by(|a, b| {
+        let (a, b) = (a.file_name(), b.file_name());
+        input_order(&a.to_string_lossy(), &b.to_string_lossy(), sort_group)
+    });
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.metadata().unwrap().is_dir() {
+            for (path, file_name) in list_input_files(&entry.path(), false, sort_group)? {
+                files.push((path, format!("{}/{}", name, file_name)));
+            }
+        } else {
+            files.push((entry.path(), name));
+        }
+    }
+    Ok(files)
+}
+
+// The order of input files: natural order, or by the `sort_group`th number
+// group first with --sort-by-group.
+fn input_order(a: &str, b: &str, sort_group: Option<usize>) -> std::cmp::Ordering {
+    let by_group = match sort_group {
+        Some(group) => {
+            let (a_number, b_number) = (number_group(a, group), number_group(b, group));
+            (a_number.is_none(), a_number).cmp(&(b_number.is_none(), b_number))
+        }
+        None => std::cmp::Ordering::Equal,
+    };
+    by_group.then_with(|| natural_cmp(a, b))
+}
+
+// A file whose name contains this starts a new output page where it sorts,
+// e.g. `walk_99---page-break---.txt` after the walk frames.
+const PAGE_BREAK_MARKER: &str = "---page-break---";
+
+// Orders names so that runs of digits compare by numeric value, which puts
+// "f2" before "f10" and treats "f01" and "f1" as the same frame number. Names
+// that only differ in zero-padding fall back to plain string order.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let a_number = take_number(&mut a_chars);
+                let b_number = take_number(&mut b_chars);
+                a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(&b_number))
+            }
+            (Some(a_char), Some(b_char)) => {
+                a_chars.next();
+                b_chars.next();
+                a_char.cmp(&b_char)
+            }
+        };
+        if ordering != std::cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// The value of the `group`th run of digits in a name, counting from 1, so
+// group 2 of `char_0003_walk_12.png` is 12. Names with fewer runs have none
+// and sort after the others.
+fn number_group(name: &str, group: usize) -> Option<u64> {
+    name.split(|char: char| !char.is_ascii_digit())
+        .filter(|digits| !digits.is_empty())
+        .nth(group - 1)
+        .map(|digits| digits.parse().unwrap_or(u64::MAX))
+}
+
+// Consumes a run of digits and returns it without leading zeros.
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(char) = chars.next_if(char::is_ascii_digit) {
+        if !(number.is_empty() && char == '0') {
+            number.push(char);
+        }
+    }
+    number
+}
+
+// Detects the format from the file's magic bytes, for files without a known
+// image extension.
+fn sniff_format(path: &std::path::Path) -> Option<image::ImageFormat> {
+    let mut header = [0u8; 16];
+    let mut file = std::fs::File::open(path).ok()?;
+    let length = std::io::Read::read(&mut file, &mut header).ok()?;
+    sniff_bytes(&header[..length])
+}
+
+fn sniff_bytes(bytes: &[u8]) -> Option<image::ImageFormat> {
+    let format = image::guess_format(bytes).ok()?;
+    is_supported_format(format).then_some(format)
+}
+
+fn is_supported_format(format: image::ImageFormat) -> bool {
+    matches!(
+        format,
+        image::ImageFormat::Png
+            | image::ImageFormat::Jpeg
+            | image::ImageFormat::Bmp
+            | image::ImageFormat::WebP
+            | image::ImageFormat::Tiff
+    )
+}
+
+fn get_image_format(str: &str) -> Option<image::ImageFormat> {
+    match str {
+        "png" => Some(image::ImageFormat::Png),
+        "jpg" | "jpeg" => Some(image::ImageFormat::Jpeg),
+        "bmp" => Some(image::ImageFormat::Bmp),
+        "webp" => Some(image::ImageFormat::WebP),
+        "tif" | "tiff" => Some(image::ImageFormat::Tiff),
+        _ => None,
+    }
+}
+
+// Decodes the images in parallel on a pool of --jobs threads (the core count
+// by default); frame order is kept. Every image that fails to decode is
+// reported, unless --fail-fast stops at the first.
+fn collect_images(
+    images_data: Vec<ImageData>,
+    settings: &Settings,
+) -> Result<Vec<Sprite>, SpritesheetErr> {
+    use rayon::prelude::*;
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(settings.jobs.unwrap_or(0))
+        .build()
+        .map_err(|_| SpritesheetErr::InvalidSettings("can't start loader threads".to_string()))?;
+    let decode_error =
+        |failure: String| SpritesheetErr::ImportError(format!("can't decode {}", failure));
+    if settings.fail_fast {
+        return pool.install(|| {
+            images_data
+                .into_par_iter()
+                .map(|image_info| load_sprite(image_info, settings).map_err(decode_error))
+                .collect()
+        });
+    }
+    let results: Vec<Result<Sprite, String>> = pool.install(|| {
+        images_data
+            .into_par_iter()
+            .map(|image_info| load_sprite(image_info, settings))
+            .collect()
+    });
+    let mut images = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(sprite) => images.push(sprite),
+            Err(failure) => errors.push(decode_error(failure)),
+        }
+    }
+    combine_errors(errors)?;
+    Ok(images)
+}
+
+// None, one or several errors collected from a whole batch of images.
+fn combine_errors(mut errors: Vec<SpritesheetErr>) -> Result<(), SpritesheetErr> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.pop().unwrap()),
+        _ => Err(SpritesheetErr::Multiple(errors)),
+    }
+}
+
+// Fails with `name (reason)` when the image can't be decoded.
+fn load_sprite(image_info: ImageData, settings: &Settings) -> Result<Sprite, String> {
+    let mut image = image_info
+        .load()
+        .map_err(|error| format!("{} ({})", image_info.name, error))?;
+    if let Some((x, y, width, height)) = settings.crop {
+        image = image.crop_imm(x, y, width, height);
+    }
+    if let Some(key) = settings.color_key.filter(|_| !image_info.placeholder) {
+        image = apply_color_key(image, key);
+    }
+    if let Some(mode) = settings.assume_alpha.filter(|_| image.color().has_alpha()) {
+        image = conform_alpha(image, mode, &image_info.name, settings);
+    }
+    let avg_color =
+        if settings.compute_avg_color || settings.sort.is_some_and(SortOrder::needs_avg_color) {
+            calculate_avg_color(&image)
+        } else {
+            None
+        };
+    // Hashed before any transform, so it identifies the source frame.
+    let hash = (settings.frame_hash && !image_info.placeholder).then(|| patch::frame_hash(&image));
+    let rule = settings
+        .sprite_scales
+        .iter()
+        .rev()
+        .find(|(pattern, _)| ignore::matches(pattern, &image_info.name))
+        .filter(|_| !image_info.placeholder);
+    let resize = |image: DynamicImage| match rule {
+        Some(&(_, scale)) => {
+            let width = ((image.width() as f32 * scale).round() as u32).max(1);
+            let height = ((image.height() as f32 * scale).round() as u32).max(1);
+            image.resize_exact(width, height, resize_filter(settings))
+        }
+        None => image,
+    };
+    let image = resize(image);
+    // The other layers get the same crop and scale, but keep their pixels.
+    let mut layers = Vec::new();
+    for layer_info in image_info.layers.iter() {
+        let mut layer = layer_info
+            .load()
+            .map_err(|error| format!("{} ({})", layer_info.name, error))?;
+        if let Some((x, y, width, height)) = settings.crop {
+            layer = layer.crop_imm(x, y, width, height);
+        }
+        let layer = resize(layer);
+        if layer.dimensions() != image.dimensions() {
+            return Err(format!(
+                "{} ({}x{}, but {} is {}x{})",
+                layer_info.name,
+                layer.width(),
+                layer.height(),
+                image_info.name,
+                image.width(),
+                image.height()
+            ));
+        }
+        layers.push(layer);
+    }
+    Ok(Sprite {
+        name: image_info.name,
+        image,
+        hash,
+        avg_color,
+        duration: image_info.duration,
+        placeholder: image_info.placeholder,
+        trim: None,
+        angle: 0.0,
+        mips: Vec::new(),
+        layers,
+    })
+}
+
+// Makes every pixel of exactly the key color fully transparent (and black),
+// for legacy sprites that mark transparency with e.g. magenta.
+fn apply_color_key(image: DynamicImage, key: [u8; 3]) -> DynamicImage {
+    if is_high_bit_depth(&image) {
+        let key = key.map(|value| value as u16 * 257);
+        let mut buffer = image.to_rgba16();
+        for pixel in buffer.pixels_mut().filter(|pixel| pixel.0[..3] == key) {
+            *pixel = image::Rgba([0, 0, 0, 0]);
+        }
+        return buffer.into();
+    }
+    let mut buffer = image.to_rgba8();
+    for pixel in buffer.pixels_mut().filter(|pixel| pixel.0[..3] == key) {
+        *pixel = image::Rgba([0, 0, 0, 0]);
+    }
+    buffer.into()
+}
+
+// Converts a sprite whose alpha doesn't look like --assume-alpha says. An image
+// with a color channel above its alpha anywhere is certainly straight. One
+// without such a pixel but with translucent ones is taken as premultiplied,
+// which is only a guess: dark straight-alpha sprites like smoke pass for
+// premultiplied too, and get brightened by the conversion.
+fn conform_alpha(
+    image: DynamicImage,
+    mode: AlphaMode,
+    name: &str,
+    settings: &Settings,
+) -> DynamicImage {
+    let converted: Option<DynamicImage> = if is_high_bit_depth(&image) {
+        conform_buffer(image.to_rgba16(), mode, name, settings).map(Into::into)
+    } else {
+        conform_buffer(image.to_rgba8(), mode, name, settings).map(Into::into)
+    };
+    converted.unwrap_or(image)
+}
+
+fn conform_buffer<T>(
+    mut buffer: image::ImageBuffer<image::Rgba<T>, Vec<T>>,
+    mode: AlphaMode,
+    name: &str,
+    settings: &Settings,
+) -> Option<image::ImageBuffer<image::Rgba<T>, Vec<T>>>
+where
+    T: image::Primitive + Into<u32> + TryFrom<u32>,
+    image::Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    let straight = postprocess::has_color_above_alpha(&buffer);
+    let translucent = buffer
+        .pixels()
+        .any(|pixel| pixel.0[3] > T::zero() && pixel.0[3] < T::DEFAULT_MAX_VALUE);
+    match mode {
+        AlphaMode::Straight if !straight && translucent => {
+            let message = format!(
+                "{} looks premultiplied, converting it to straight alpha",
+                name
+            );
+            warn(settings, message, Some(name));
+            postprocess::unpremultiply_alpha(&mut buffer);
+        }
+        AlphaMode::Premultiplied if straight => {
+            let message = format!("{} has straight alpha, premultiplying it", name);
+            warn(settings, message, Some(name));
+            postprocess::premultiply_alpha(&mut buffer);
+        }
+        _ => return None,
+    }
+    Some(buffer)
+}
+
+// Whether the saved sheet holds premultiplied colors, either made so by
+// --premultiply or packed from premultiplied sources.
+fn premultiplied_sheet(settings: &Settings) -> bool {
+    settings.premultiply || settings.assume_alpha == Some(AlphaMode::Premultiplied)
+}
+
+// Rejects every sprite with a side longer than `max_dimension` (only the
+// first with --fail-fast), or with --oversized downscale shrinks them
+// (keeping their aspect ratio) to fit.
+fn limit_sprite_dimensions(
+    images: &mut [Sprite],
+    max_dimension: u32,
+    settings: &Settings,
+) -> Result<(), SpritesheetErr> {
+    let filter = resize_filter(settings);
+    let mut errors = Vec::new();
+    for sprite in images.iter_mut() {
+        let (width, height) = sprite.image.dimensions();
+        if width <= max_dimension && height <= max_dimension {
+            continue;
+        }
+        if !settings.downscale_oversized {
+            let error =
+                SpritesheetErr::SpriteTooLarge(sprite.name.clone(), width, height, max_dimension);
+            if settings.fail_fast {
+                return Err(error);
+            }
+            errors.push(error);
+            continue;
+        }
+        let message = format!(
+            "downscaling {} from {}x{} to fit {}",
+            sprite.name, width, height, max_dimension
+        );
+        warn(settings, message, Some(&sprite.name));
+        sprite.image = sprite.image.resize(max_dimension, max_dimension, filter);
+    }
+    combine_errors(errors)
+}
+
+// Fails when the images don't all share one color type, listing the ones that
+// differ from the most common type.
+fn check_color_types(images: &[Sprite]) -> Result<(), SpritesheetErr> {
+    let mut color_counts: Vec<(image::ColorType, usize)> = Vec::new();
+    for sprite in images.iter().filter(|sprite| !sprite.placeholder) {
+        match color_counts
+            .iter_mut()
+            .find(|entry| entry.0 == sprite.image.color())
+        {
+            Some(entry) => entry.1 += 1,
+            None => color_counts.push((sprite.image.color(), 1)),
+        }
+    }
+    if color_counts.len() <= 1 {
+        return Ok(());
+    }
+
+    let expected = color_counts.iter().max_by_key(|entry| entry.1).unwrap().0;
+    let offenders: Vec<String> = images
+        .iter()
+        .filter(|sprite| !sprite.placeholder && sprite.image.color() != expected)
+        .map(|sprite| format!("{} ({:?})", sprite.name, sprite.image.color()))
+        .collect();
+    Err(SpritesheetErr::MixedColorTypes(format!(
+        "expected {:?}, found {}",
+        expected,
+        offenders.join(", ")
+    )))
+}
+
+fn calculate_avg_color(image: &DynamicImage) -> Option<[u8; 3]> {
+    let mut sum = [0u64; 3];
+    let mut count = 0u64;
+    for (_, _, pixel) in image.pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        for channel in 0..3 {
+            sum[channel] += pixel[channel] as u64;
+        }
+        count += 1;
+    }
+
+    if count == 0 {
+        return None;
+    }
+    Some(sum.map(|value| (value / count) as u8))
+}
+
+fn sort_images(images: &mut [Sprite], sort: SortOrder) {
+    let key = |sprite: &Sprite| {
+        let [red, green, blue] = sprite
+            .avg_color
+            .unwrap_or([0, 0, 0])
+            .map(|value| value as f32);
+        match sort {
+            SortOrder::Luminance => 0.2126 * red + 0.7152 * green + 0.0722 * blue,
+            SortOrder::Hue => calculate_hue(red, green, blue),
+            // Already ordered before loading.
+            SortOrder::ExifTime => 0.0,
+        }
+    };
+    images.sort_by(|a, b| key(a).total_cmp(&key(b)));
+}
+
+fn calculate_hue(red: f32, green: f32, blue: f32) -> f32 {
+    let max = red.max(green).max(blue);
+    let delta = max - red.min(green).min(blue);
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let hue = if max == red {
+        ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        (blue - red) / delta + 2.0
+    } else {
+        (red - green) / delta + 4.0
+    };
+    hue * 60.0
+}
+
+// Center-crops every sprite to the smallest width and the smallest height
+// among them (--fit smallest-crop), so frames of any size share one cell
+// without being dropped or scaled. Odd margins leave the extra pixel on the
+// right and bottom.
+fn crop_to_smallest(mut images: Vec<Sprite>) -> (Vec<Sprite>, (u32, u32)) {
+    let sprites = || images.iter().filter(|sprite| !sprite.placeholder);
+    let width = sprites().map(|sprite| sprite.image.width()).min();
+    let height = sprites().map(|sprite| sprite.image.height()).min();
+    let cell = (width.unwrap_or(0), height.unwrap_or(0));
+    for sprite in images.iter_mut().filter(|sprite| !sprite.placeholder) {
+        let (width, height) = sprite.image.dimensions();
+        if (width, height) != cell {
+            let (x, y) = ((width - cell.0) / 2, (height - cell.1) / 2);
+            sprite.image = sprite.image.crop_imm(x, y, cell.0, cell.1);
+        }
+    }
+    (images, cell)
+}
+
+// Removes the images of every --exclude-resolution size, before anything
+// looks at which resolution is the most common.
+fn drop_excluded_resolutions(images: Vec<Sprite>, settings: &Settings) -> Vec<Sprite> {
+    images
+        .into_iter()
+        .filter(|sprite| {
+            sprite.placeholder
+                || !settings
+                    .excluded_resolutions
+                    .contains(&sprite.image.dimensions())
+        })
+        .collect()
+}
+
+fn filter_images(
+    images: Vec<Sprite>,
+    preflight_total: Option<u32>,
+    settings: &Settings,
+    skipped: &mut Vec<String>,
+) -> Result<Vec<Sprite>, SpritesheetErr> {
+    let images = drop_excluded_resolutions(images, settings);
+    let mut resolution_map: std::collections::HashMap<(u32, u32), u32> =
+        std::collections::HashMap::new();
+    for sprite in images.iter().filter(|sprite| !sprite.placeholder) {
+        let key = resolution_map
+            .entry((sprite.image.height(), sprite.image.width()))
+            .or_default();
+        *key += 1;
+    }
+    if resolution_map.is_empty() {
+        return Err(SpritesheetErr::NoImagesFound);
+    }
+
+    let popular_resolution = kept_resolution(&resolution_map, settings)?;
+
+    let mut filtered_images = Vec::new();
+
+    for sprite in images {
+        if sprite.placeholder
+            || sprite.image.height() == popular_resolution.0
+                && sprite.image.width() == popular_resolution.1
+        {
+            filtered_images.push(sprite);
+        } else {
+            let (width, height) = sprite.image.dimensions();
+            skipped.push(format!("{} ({}x{})", sprite.name, width, height));
+        }
+    }
+
+    let total = preflight_total.unwrap_or(resolution_map.values().sum());
+    let kept = resolution_map[&popular_resolution];
+    print_selection(
+        (popular_resolution.1, popular_resolution.0),
+        kept,
+        total,
+        settings,
+    );
+    if resolution_map.len() > 1 {
+        let message = format!(
+            "keeping {} images of {}x{}, found {}",
+            filtered_images.len(),
+            popular_resolution.1,
+            popular_resolution.0,
+            describe_resolutions(&resolution_map)
+        );
+        warn(settings, message, None);
+    }
+    Ok(filtered_images)
+}
+
+// Reports the packed resolution. `total` counts every image that was sized,
+// so images dropped before decoding (--preflight, --stream) show up too.
+fn print_selection(resolution: (u32, u32), kept: u32, total: u32, settings: &Settings) {
+    let reason = match (settings.cell_size, settings.keep) {
+        (Some(_), _) => "set by --cell-size",
+        (None, KeepResolution::MostCommon) => "the most common",
+        (None, KeepResolution::Largest) => "the largest",
+        (None, KeepResolution::Smallest) => "the smallest",
+    };
+    println!(
+        "Selected {}x{} ({} of {} images, {}); dropped {}.",
+        resolution.0,
+        resolution.1,
+        kept,
+        total,
+        reason,
+        total - kept
+    );
+}
+
+// Picks the (height, width) bucket to pack according to --keep and
+// --cell-size.
+fn kept_resolution(
+    resolution_map: &std::collections::HashMap<(u32, u32), u32>,
+    settings: &Settings,
+) -> Result<(u32, u32), SpritesheetErr> {
+    let max_popular_value = resolution_map.values().max().unwrap();
+
+    let mut popular_resolution = match settings.keep {
+        KeepResolution::MostCommon => {
+            let mut candidates: Vec<(u32, u32)> = resolution_map
+                .iter()
+                .filter(|entry| entry.1 == max_popular_value)
+                .map(|entry| *entry.0)
+                .collect();
+            // The map's order changes from run to run, so ties would pick
+            // a different resolution each time.
+            if settings.deterministic {
+                candidates.sort_unstable();
+            }
+            candidates[0]
+        }
+        KeepResolution::Largest => *resolution_map
+            .keys()
+            .max_by_key(|(height, width)| (*width as u64 * *height as u64, *width))
+            .unwrap(),
+        KeepResolution::Smallest => *resolution_map
+            .keys()
+            .min_by_key(|(height, width)| (*width as u64 * *height as u64, *width))
+            .unwrap(),
+    };
+    if let Some((width, height)) = settings.cell_size {
+        popular_resolution = if resolution_map.contains_key(&(height, width)) {
+            (height, width)
+        } else {
+            (0, 0)
+        };
+    }
+
+    if popular_resolution == (0, 0) {
+        return Err(SpritesheetErr::FilterImages(describe_resolutions(
+            resolution_map,
+        )));
+    }
+    Ok(popular_resolution)
+}
+
+// Lists every (height, width) bucket with its image count, most common first.
+fn describe_resolutions(resolution_map: &std::collections::HashMap<(u32, u32), u32>) -> String {
+    let mut resolutions: Vec<_> = resolution_map.iter().collect();
+    resolutions.sort_by_key(|(resolution, count)| (std::cmp::Reverse(**count), **resolution));
+    resolutions
+        .iter()
+        .map(|((height, width), count)| format!("{}x{}: {}", width, height, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Size of an image after --crop.
+fn cropped_dimensions(dimensions: (u32, u32), settings: &Settings) -> (u32, u32) {
+    match settings.crop {
+        Some((x, y, width, height)) => (
+            width.min(dimensions.0.saturating_sub(x)),
+            height.min(dimensions.1.saturating_sub(y)),
+        ),
+        None => dimensions,
+    }
+}
+
+// Reads only the image headers, so unreadable files fail before anything is
+// decoded, and images of resolutions that won't be packed are dropped without
+// decoding their pixels. Also returns how many images were sized.
+fn preflight(
+    images_data: Vec<ImageData>,
+    settings: &Settings,
+    skipped: &mut Vec<String>,
+) -> Result<(Vec<ImageData>, u32), SpritesheetErr> {
+    let mut sized_images = Vec::new();
+    let mut errors = Vec::new();
+    for image_info in images_data {
+        if image_info.placeholder {
+            sized_images.push((image_info, None));
+            continue;
+        }
+        match image_info.dimensions() {
+            Ok(dimensions) => {
+                let dimensions = cropped_dimensions(dimensions, settings);
+                if settings.excluded_resolutions.contains(&dimensions) {
+                    continue;
+                }
+                if let Some(max_dimension) = settings.max_sprite_dim {
+                    if dimensions.0.max(dimensions.1) > max_dimension
+                        && !settings.downscale_oversized
+                    {
+                        errors.push(SpritesheetErr::SpriteTooLarge(
+                            image_info.name,
+                            dimensions.0,
+                            dimensions.1,
+                            max_dimension,
+                        ));
+                        if settings.fail_fast {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+                sized_images.push((image_info, Some(dimensions)));
+            }
+            Err(error) => {
+                errors.push(SpritesheetErr::ImportError(format!(
+                    "can't read {} ({})",
+                    image_info.name, error
+                )));
+                if settings.fail_fast {
+                    break;
+                }
+            }
+        }
+    }
+    combine_errors(errors)?;
+
+    let mut resolution_map = std::collections::HashMap::new();
+    for (_, dimensions) in sized_images.iter() {
+        if let Some((width, height)) = dimensions {
+            *resolution_map.entry((*height, *width)).or_default() += 1;
+        }
+    }
+    let total = resolution_map.values().sum();
+    if settings.uniform_max || resolution_map.is_empty() {
+        return Ok((
+            sized_images.into_iter().map(|entry| entry.0).collect(),
+            total,
+        ));
+    }
+
+    let (height, width) = kept_resolution(&resolution_map, settings)?;
+    let mut filtered_images = Vec::new();
+    for (image_info, dimensions) in sized_images {
+        match dimensions {
+            Some(dimensions) if dimensions != (width, height) => skipped.push(format!(
+                "{} ({}x{})",
+                image_info.name, dimensions.0, dimensions.1
+            )),
+            _ => filtered_images.push(image_info),
+        }
+    }
+    if resolution_map.len() > 1 {
+        let message = format!(
+            "keeping {} images of {}x{}, found {}",
+            filtered_images.len(),
+            width,
+            height,
+            describe_resolutions(&resolution_map)
+        );
+        warn(settings, message, None);
+    }
+    Ok((filtered_images, total))
+}
+
+fn filter_image_paths(
+    images_data: Vec<ImageData>,
+    cell: (u32, u32),
+    settings: &Settings,
+    skipped: &mut Vec<String>,
+) -> Result<Vec<ImageData>, SpritesheetErr> {
+    let mut filtered_images = Vec::new();
+    let mut resolution_map = std::collections::HashMap::new();
+    for image_info in images_data {
+        if image_info.placeholder {
+            filtered_images.push(image_info);
+            continue;
+        }
+        let dimensions = image_info.dimensions().map_err(|error| {
+            SpritesheetErr::ImportError(format!("can't read {} ({})", image_info.name, error))
+        })?;
+        let dimensions = cropped_dimensions(dimensions, settings);
+        if settings.excluded_resolutions.contains(&dimensions) {
+            continue;
+        }
+        *resolution_map
+            .entry((dimensions.1, dimensions.0))
+            .or_default() += 1;
+        if dimensions == cell {
+            filtered_images.push(image_info);
+        } else {
+            skipped.push(format!(
+                "{} ({}x{})",
+                image_info.name, dimensions.0, dimensions.1
+            ));
+        }
+    }
+
+    if filtered_images
+        .iter()
+        .all(|image_info| image_info.placeholder)
+    {
+        return Err(SpritesheetErr::FilterImages(describe_resolutions(
+            &resolution_map,
+        )));
+    }
+    let total = resolution_map.values().sum();
+    let kept = resolution_map.get(&(cell.1, cell.0)).copied().unwrap_or(0);
+    print_selection(cell, kept, total, settings);
+    if resolution_map.len() > 1 {
+        let message = format!(
+            "keeping {} images of {}x{}, found {}",
+            filtered_images.len(),
+            cell.0,
+            cell.1,
+            describe_resolutions(&resolution_map)
+        );
+        warn(settings, message, None);
+    }
+    Ok(filtered_images)
+}
+
+// With `serpentine` odd rows run right to left, matching the wiring of
+// serpentine LED matrices.
+fn calculate_layout(
+    row_count: u32,
+    images_count: usize,
+    cell: (u32, u32),
+    serpentine: bool,
+) -> Layout {
+    let height = (images_count as f32 / row_count as f32).ceil() as u32;
+
+    let mut positions = Vec::new();
+    for y in 0..height {
+        for x in 0..row_count {
+            if images_count <= positions.len() {
+                break;
+            }
+            let column = if serpentine && y % 2 == 1 {
+                row_count - 1 - x
+            } else {
+                x
+            };
+            positions.push((column * cell.0, y * cell.1));
+        }
+    }
+
+    Layout {
+        columns: row_count,
+        rows: height,
+        cell_width: cell.0,
+        cell_height: cell.1,
+        width: row_count * cell.0,
+        height: height * cell.1,
+        positions,
+        scale: (1.0, 1.0),
+        frame_heights: Vec::new(),
+        ring_angles: Vec::new(),
+        page: None,
+        sprite_scale: None,
+        cropped_frames: Vec::new(),
+    }
+}
+
+// The whole canvas is cleared to the background (transparent unless
+// --background is given) before any sprite is copied, so cells without a
+// sprite are guaranteed to hold exactly that color. Sprites replace the
+// background pixels they cover instead of being blended over them.
+//
+// The buffer is reserved up front so a sheet too large for the machine (or
+// for --max-alloc) fails with an error instead of an allocator abort.
+fn new_canvas(layout: &Layout, settings: &Settings) -> Result<DynamicImage, SpritesheetErr> {
+    let background = settings.background.unwrap_or(image::Rgba([0, 0, 0, 0]));
+    let channel_bytes = canvas_channel_bytes(settings);
+    let bytes = layout.width as u64 * layout.height as u64 * 4 * channel_bytes;
+    let too_large = || SpritesheetErr::SheetTooLarge(layout.width, layout.height, bytes);
+    if settings
+        .max_alloc
+        .is_some_and(|max_alloc| bytes > max_alloc)
+    {
+        return Err(too_large());
+    }
+    let length = usize::try_from(bytes / channel_bytes).map_err(|_| too_large())?;
+
+    if settings.bit_depth == Some(16) {
+        let background = background.0.map(|value| value as u16 * 257);
+        let data = filled_buffer(length, background).ok_or_else(too_large)?;
+        let buffer: image::ImageBuffer<image::Rgba<u16>, Vec<u16>> =
+            image::ImageBuffer::from_raw(layout.width, layout.height, data).unwrap();
+        return Ok(buffer.into());
+    }
+    let data = filled_buffer(length, background.0).ok_or_else(too_large)?;
+    Ok(
+        image::RgbaImage::from_raw(layout.width, layout.height, data)
+            .unwrap()
+            .into(),
+    )
+}
+
+fn canvas_channel_bytes(settings: &Settings) -> u64 {
+    if settings.bit_depth == Some(16) {
+        2
+    } else {
+        1
+    }
+}
+
+fn filled_buffer<T: Copy>(length: usize, pixel: [T; 4]) -> Option<Vec<T>> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(length).ok()?;
+    data.extend(pixel.iter().cycle().take(length));
+    Some(data)
+}
+
+// A row of cells for --padding-color: its top and bottom edge and the left
+// and right edge of every cell in it.
+struct CellRow {
+    top: u32,
+    bottom: u32,
+    cells: Vec<(u32, u32)>,
+}
+
+// Paints the --padding gaps with --padding-color once the canvas is cleared:
+// the strips between the cells of a row and between rows, and around the grid
+// with --outer-padding. Empty cells keep the background.
+fn fill_padding(spritesheet: &mut DynamicImage, layout: &Layout, settings: &Settings) {
+    let Some(color) = settings.padding_color else {
+        return;
+    };
+    let mut rows: Vec<CellRow> = Vec::new();
+    for (index, &(x, y)) in layout.positions.iter().enumerate() {
+        if layout.is_cropped(index) {
+            continue;
+        }
+        let (width, height) = layout.frame_size(index);
+        match rows.iter_mut().find(|row| row.top == y) {
+            Some(row) => {
+                row.bottom = row.bottom.max(y + height);
+                row.cells.push((x, x + width));
+            }
+            None => rows.push(CellRow {
+                top: y,
+                bottom: y + height,
+                cells: vec![(x, x + width)],
+            }),
+        }
+    }
+    rows.sort_unstable_by_key(|row| row.top);
+    let cells = || rows.iter().flat_map(|row| row.cells.iter());
+    let (Some(left), Some(right)) = (
+        cells().map(|cell| cell.0).min(),
+        cells().map(|cell| cell.1).max(),
+    ) else {
+        return;
+    };
+    let (top, bottom) = (rows[0].top, rows[rows.len() - 1].bottom);
+
+    // (x, y, width, height) of every strip.
+    let mut gaps = Vec::new();
+    for row in rows.iter_mut() {
+        row.cells.sort_unstable();
+        for pair in row.cells.windows(2) {
+            if pair[0].1 < pair[1].0 {
+                gaps.push((
+                    pair[0].1,
+                    row.top,
+                    pair[1].0 - pair[0].1,
+                    row.bottom - row.top,
+                ));
+            }
+        }
+    }
+    for pair in rows.windows(2) {
+        if pair[0].bottom < pair[1].top {
+            gaps.push((
+                left,
+                pair[0].bottom,
+                right - left,
+                pair[1].top - pair[0].bottom,
+            ));
+        }
+    }
+    let padding = match settings.outer_padding {
+        true => settings.padding,
+        false => (0, 0),
+    };
+    let outer = (
+        left.saturating_sub(padding.0),
+        top.saturating_sub(padding.1),
+        (right + padding.0).min(layout.width),
+        (bottom + padding.1).min(layout.height),
+    );
+    gaps.push((outer.0, outer.1, outer.2 - outer.0, top - outer.1));
+    gaps.push((outer.0, bottom, outer.2 - outer.0, outer.3 - bottom));
+    gaps.push((outer.0, top, left - outer.0, bottom - top));
+    gaps.push((right, top, outer.2 - right, bottom - top));
+
+    for gap in gaps {
+        fill_rect(spritesheet, gap, color);
+    }
+}
+
+// Fills a rect of the canvas with one color, a row of pixels at a time.
+fn fill_rect(spritesheet: &mut DynamicImage, rect: (u32, u32, u32, u32), color: image::Rgba<u8>) {
+    match spritesheet {
+        DynamicImage::ImageRgba8(canvas) => fill_buffer(canvas, rect, color),
+        DynamicImage::ImageRgba16(canvas) => fill_buffer(
+            canvas,
+            rect,
+            image::Rgba(color.0.map(|value| value as u16 * 257)),
+        ),
+        canvas => {
+            for y in rect.1..rect.1 + rect.3 {
+                for x in rect.0..rect.0 + rect.2 {
+                    canvas.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+fn fill_buffer<P: image::Pixel>(
+    canvas: &mut image::ImageBuffer<P, Vec<P::Subpixel>>,
+    rect: (u32, u32, u32, u32),
+    pixel: P,
+) {
+    let (x, y, width, height) = rect;
+    let channels = P::CHANNEL_COUNT as usize;
+    let stride = canvas.width() as usize * channels;
+    let data: &mut [P::Subpixel] = canvas;
+    for row in y as usize..(y + height) as usize {
+        let start = row * stride + x as usize * channels;
+        let end = start + width as usize * channels;
+        for value in data[start..end].chunks_exact_mut(channels) {
+            value.copy_from_slice(pixel.channels());
+        }
+    }
+}
+
+// Copies through the canvas' own pixel type, so 16-bit sprites keep their
+// precision on a 16-bit canvas instead of passing through 8-bit pixels.
+fn copy_sprite(spritesheet: &mut DynamicImage, sprite: &DynamicImage, position: (u32, u32)) {
+    match spritesheet {
+        DynamicImage::ImageRgba16(canvas) => {
+            canvas.copy_from(&sprite.to_rgba16(), position.0, position.1)
+        }
+        canvas => canvas.copy_from(sprite, position.0, position.1),
+    }
+    .unwrap();
+}
+
+// Rounds both sides of the cell up to a multiple of --cell-multiple, e.g. to
+// keep every cell on 4x4 blocks for block-compressed textures.
+fn rounded_cell(cell: (u32, u32), settings: &Settings) -> (u32, u32) {
+    match settings.cell_multiple {
+        Some(multiple) => (
+            cell.0.next_multiple_of(multiple),
+            cell.1.next_multiple_of(multiple),
+        ),
+        None => cell,
+    }
+}
+
+// Sprites smaller than the cell (with --uniform-max) sit in its top-left
+// corner, or in its center with --align center. Cells enlarged by
+// --cell-multiple always center them.
+fn align_in_cell(
+    layout: &Layout,
+    sprite: &DynamicImage,
+    index: usize,
+    settings: &Settings,
+) -> (u32, u32) {
+    let position = layout.positions[index];
+    let sprite_area_height = layout.frame_size(index).1 - label_height(settings);
+    match (settings.align, settings.cell_multiple) {
+        (Align::TopLeft, None) => position,
+        _ => (
+            position.0 + layout.cell_width.saturating_sub(sprite.width()) / 2,
+            position.1 + sprite_area_height.saturating_sub(sprite.height()) / 2,
+        ),
+    }
+}
+
+// --pixel-art always resizes with nearest neighbor, whatever --filter says.
+fn resize_filter(settings: &Settings) -> image::imageops::FilterType {
+    if settings.pixel_art {
+        return image::imageops::FilterType::Nearest;
+    }
+    settings
+        .filter
+        .unwrap_or(image::imageops::FilterType::Lanczos3)
+}
+
+fn label_height(settings: &Settings) -> u32 {
+    if settings.labels {
+        labels::LABEL_HEIGHT
+    } else {
+        0
+    }
+}
+
+fn place_sprite(
+    spritesheet: &mut DynamicImage,
+    layout: &Layout,
+    sprite: &Sprite,
+    index: usize,
+    settings: &Settings,
+) {
+    copy_sprite(
+        spritesheet,
+        &sprite.image,
+        align_in_cell(layout, &sprite.image, index, settings),
+    );
+    if settings.labels {
+        // Dark text, unless the background is dark enough to need light text.
+        let background = settings.background.unwrap_or(image::Rgba([0, 0, 0, 0]));
+        let is_dark = background[3] > 127
+            && (background[0] as u32 + background[1] as u32 + background[2] as u32) < 384;
+        let color = if is_dark {
+            image::Rgba([255, 255, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        };
+        let position = layout.positions[index];
+        labels::draw_label(
+            spritesheet,
+            &sprite.name,
+            position.0,
+            position.1 + layout.frame_size(index).1 - labels::LABEL_HEIGHT,
+            layout.cell_width,
+            color,
+        );
+    }
+}
+
+fn is_high_bit_depth(image: &DynamicImage) -> bool {
+    image.color().bytes_per_pixel() > image.color().channel_count()
+}
+
+fn warn_bit_depth(high_bit_depth_count: usize, settings: &Settings) {
+    if high_bit_depth_count > 0 && settings.bit_depth.is_none() {
+        let message = format!(
+            "{} images have more than 8 bits per channel and are reduced to 8 bits, use --bit-depth 16 to keep them",
+            high_bit_depth_count
+        );
+        warn(settings, message, None);
+    }
+}
+
+fn create_spritesheet(
+    layout: &Layout,
+    images: &[Sprite],
+    settings: &Settings,
+) -> Result<image::DynamicImage, SpritesheetErr> {
+    let mut spritesheet = new_canvas(layout, settings)?;
+    fill_padding(&mut spritesheet, layout, settings);
+
+    for (index, sprite) in images.iter().enumerate().take(layout.positions.len()) {
+        if !layout.is_cropped(index) {
+            place_sprite(&mut spritesheet, layout, sprite, index, settings);
+        }
+    }
+
+    let high_bit_depth_count = images
+        .iter()
+        .filter(|sprite| is_high_bit_depth(&sprite.image))
+        .count();
+    warn_bit_depth(high_bit_depth_count, settings);
+
+    Ok(spritesheet)
+}
+
+fn stream_spritesheet(
+    layout: &Layout,
+    images_data: Vec<ImageData>,
+    settings: &Settings,
+) -> Result<(image::DynamicImage, Vec<Sprite>), SpritesheetErr> {
+    let mut spritesheet = new_canvas(layout, settings)?;
+    fill_padding(&mut spritesheet, layout, settings);
+    let mut images = Vec::new();
+    let mut high_bit_depth_count = 0;
+
+    for (index, image_info) in images_data
+        .into_iter()
+        .enumerate()
+        .take(layout.positions.len())
+    {
+        let mut sprite = load_sprite(image_info, settings)
+            .map_err(|failure| SpritesheetErr::ImportError(format!("can't decode {}", failure)))?;
+        place_sprite(&mut spritesheet, layout, &sprite, index, settings);
+        if is_high_bit_depth(&sprite.image) {
+            high_bit_depth_count += 1;
+        }
+        sprite.image = image::DynamicImage::new_rgba8(0, 0);
+        images.push(sprite);
+    }
+    warn_bit_depth(high_bit_depth_count, settings);
+
+    Ok((spritesheet, images))
+}
+
+fn calculate_row_count(images_count: usize) -> u32 {
+    (images_count as f32).sqrt().floor() as u32
+}
+
+// The smallest column count whose square holds every frame, so the rows
+// (rounded up) never outnumber the columns.
+fn calculate_square_column_count(images_count: usize) -> u32 {
+    let mut columns = (images_count as f64).sqrt() as u32;
+    while (columns as usize) * (columns as usize) < images_count {
+        columns += 1;
+    }
+    columns.max(1)
+}
+
+// Sheets whose sides differ by more than this factor aren't considered by
+// --optimize-fit.
+const MAX_FIT_ASPECT: f64 = 2.0;
+
+// The column count that leaves the fewest empty cells in the last row, among
+// the grids no more elongated than MAX_FIT_ASPECT; ties go to the squarer,
+// then the wider sheet. Falls back to the square grid when none qualifies.
+fn calculate_fit_column_count(images_count: usize, cell: (u32, u32)) -> u32 {
+    let count = images_count.max(1) as u64;
+    let elongation = |columns: u64| {
+        let rows = count.div_ceil(columns);
+        let ratio = (columns * cell.0 as u64) as f64 / (rows * cell.1.max(1) as u64) as f64;
+        ratio.ln().abs()
+    };
+    (1..=count)
+        .filter(|&columns| elongation(columns) <= MAX_FIT_ASPECT.ln())
+        .min_by(|&a, &b| {
+            let waste = |columns: u64| columns * count.div_ceil(columns) - count;
+            waste(a)
+                .cmp(&waste(b))
+                .then(elongation(a).total_cmp(&elongation(b)))
+                .then(b.cmp(&a))
+        })
+        .map_or_else(
+            || calculate_square_column_count(images_count),
+            |columns| columns as u32,
+        )
+}
+
+fn calculate_aspect_row_count(images_count: usize, cell: (u32, u32), aspect: (u32, u32)) -> u32 {
+    let target = (aspect.0 as f64 / aspect.1 as f64).ln();
+    let deviation = |columns: u32| {
+        let rows = (images_count as f64 / columns as f64).ceil();
+        let ratio = (columns * cell.0) as f64 / (rows * cell.1 as f64);
+        (ratio.ln() - target).abs()
+    };
+    (1..=images_count.max(1) as u32)
+        .min_by(|a, b| deviation(*a).total_cmp(&deviation(*b)))
+        .unwrap()
+}
+
+fn output_path(settings: &Settings) -> std::path::PathBuf {
+    let mut path_to_save = std::path::PathBuf::new();
+    path_to_save.push(std::env::current_dir().unwrap());
+    match (&settings.output_path, settings.input_dirs.first()) {
+        (Some(path), _) => path_to_save.push(path),
+        (None, Some(input_dir)) if settings.output_in_input => {
+            path_to_save.push(input_dir);
+            path_to_save.push("spritesheet.png");
+        }
+        (None, _) => path_to_save.push("spritesheet.png"),
+    }
+    path_to_save
+}
+
+// Asks before replacing a file, but only when someone can answer.
+fn confirm_overwrite(path: &std::path::Path) -> bool {
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return false;
+    }
+    print!("{} already exists, overwrite it? [y/N] ", path.display());
+    _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).unwrap_or_default();
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+fn save_image(
+    image: image::DynamicImage,
+    path_to_save: &std::path::Path,
+    metadata: Option<String>,
+    settings: &Settings,
+) -> Result<(), SpritesheetErr> {
+    if settings.no_overwrite && path_to_save.exists() && !confirm_overwrite(path_to_save) {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "{} already exists, not overwriting it (--no-overwrite)",
+            path_to_save.display()
+        )));
+    }
+    let is_png = path_to_save
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("png"))
+        .unwrap_or(false);
+    if metadata.is_some() || settings.palette_path.is_some() || settings.deterministic && is_png {
+        if !is_png {
+            return Err(SpritesheetErr::InvalidSettings(
+                "--embed-metadata and --palette need a .png output".to_string(),
+            ));
+        }
+        let palette = match &settings.palette_path {
+            Some(path) => Some(palette::read_palette(path)?),
+            None => None,
+        };
+        return metadata::save_png(
+            &image,
+            path_to_save,
+            metadata,
+            palette.as_deref(),
+            settings.dither,
+        );
+    }
+
+    let is_webp = path_to_save
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("webp"))
+        .unwrap_or(false);
+    if is_webp {
+        let webp = webp::encode(&image.to_rgba8(), settings.near_lossless)?;
+        return std::fs::write(path_to_save, webp).map_err(|_| SpritesheetErr::ImageSaveError);
+    }
+    let is_raw = path_to_save.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("raw") || extension.eq_ignore_ascii_case("bin")
+    });
+    if is_raw {
+        let raw = raw::encode(&image.to_rgba8());
+        return std::fs::write(path_to_save, raw).map_err(|_| SpritesheetErr::ImageSaveError);
+    }
+
+    Ok(image.save(path_to_save)?)
+}
+
+pub enum SpritesheetErr {
+    NoImagesFound,
+    FilterImages(String),
+    ImageSaveError,
+    ParseError,
+    InvalidArgument(String),
+    AtlasSaveError,
+    ImportError(String),
+    InvalidSettings(String),
+    LayoutFileError(String),
+    TooManySprites(usize, usize),
+    TooFewImages(usize, usize),
+    SpriteTooLarge(String, u32, u32, u32),
+    ImagesDiffer,
+    ValidationFailed(usize),
+    BatchFailed(usize, usize),
+    SkippedInputs(Vec<String>),
+    Multiple(Vec<SpritesheetErr>),
+    MixedColorTypes(String),
+    SheetTooLarge(u32, u32, u64),
+}
+
+impl From<image::ImageError> for SpritesheetErr {
+    fn from(_: image::ImageError) -> Self {
+        SpritesheetErr::ImageSaveError
+    }
+}
+
+impl From<ParseIntError> for SpritesheetErr {
+    fn from(_: ParseIntError) -> Self {
+        SpritesheetErr::ParseError
+    }
+}
+
+#[derive(Debug)]
+struct ImageData {
+    name: String,
+    path: std::path::PathBuf,
+    format: image::ImageFormat,
+    duration: Option<u32>,
+    bytes: Option<Vec<u8>>,
+    placeholder: bool,
+    page_break: bool,
+    // With --layers, the frame's files on the other layers, in order.
+    layers: Vec<ImageData>,
+}
+
+impl ImageData {
+    // An empty frame that keeps its cell in the layout but copies no pixels.
+    fn placeholder(name: String) -> ImageData {
+        ImageData {
+            name,
+            path: std::path::PathBuf::new(),
+            format: image::ImageFormat::Png,
+            duration: None,
+            bytes: None,
+            placeholder: true,
+            page_break: false,
+            layers: Vec::new(),
+        }
+    }
+
+    fn load(&self) -> image::ImageResult<DynamicImage> {
+        if self.placeholder {
+            return Ok(DynamicImage::new_rgba8(0, 0));
+        }
+        let result = match &self.bytes {
+            Some(bytes) => image::load_from_memory_with_format(bytes, self.format),
+            None => {
+                let file = std::fs::File::open(&self.path)?;
+                image::load(std::io::BufReader::new(file), self.format)
+            }
+        };
+        if result.is_ok() {
+            return result;
+        }
+
+        // The extension may be wrong, retry with the format the data says it is.
+        let data = self.raw_data()?;
+        match image::guess_format(&data) {
+            Ok(format) if format != self.format && is_supported_format(format) => {
+                image::load_from_memory_with_format(&data, format)
+            }
+            _ => result,
+        }
+    }
+
+    fn dimensions(&self) -> image::ImageResult<(u32, u32)> {
+        let result = match &self.bytes {
+            Some(bytes) => image::io::Reader::with_format(std::io::Cursor::new(bytes), self.format)
+                .into_dimensions(),
+            None => {
+                let file = std::fs::File::open(&self.path)?;
+                image::io::Reader::with_format(std::io::BufReader::new(file), self.format)
+                    .into_dimensions()
+            }
+        };
+        if result.is_ok() {
+            return result;
+        }
+
+        let data = self.raw_data()?;
+        match image::guess_format(&data) {
+            Ok(format) if format != self.format && is_supported_format(format) => {
+                image::io::Reader::with_format(std::io::Cursor::new(&data[..]), format)
+                    .into_dimensions()
+            }
+            _ => result,
+        }
+    }
+
+    fn raw_data(&self) -> std::io::Result<std::borrow::Cow<'_, [u8]>> {
+        Ok(match &self.bytes {
+            Some(bytes) => std::borrow::Cow::Borrowed(bytes.as_slice()),
+            None => std::borrow::Cow::Owned(std::fs::read(&self.path)?),
+        })
+    }
+}
+
+struct Sprite {
+    name: String,
+    image: DynamicImage,
+    hash: Option<u64>,
+    avg_color: Option<[u8; 3]>,
+    duration: Option<u32>,
+    placeholder: bool,
+    trim: Option<trim::Trim>,
+    // Clockwise rotation in degrees applied by --rotate.
+    angle: f32,
+    // With --mips, the rects of the full-size frame and its mip levels
+    // within the image.
+    mips: Vec<(u32, u32, u32, u32)>,
+    // With --layers, the frame on the other layers, the same size as image.
+    layers: Vec<DynamicImage>,
+}
+
+#[derive(Clone)]
+pub struct Layout {
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+    cell_height: u32,
+    width: u32,
+    height: u32,
+    positions: Vec<(u32, u32)>,
+    // Factors the saved image was resized by relative to these coordinates,
+    // horizontally and vertically.
+    scale: (f32, f32),
+    // Per-frame cell heights when --optimize-rows or --last-row-height auto
+    // shortens rows, otherwise empty and every cell is cell_height tall.
+    frame_heights: Vec<u32>,
+    // Per-frame angles in degrees, clockwise from 12 o'clock, with --layout
+    // radial, otherwise empty.
+    ring_angles: Vec<f32>,
+    // Index of this sheet when the frames are split over several pages.
+    page: Option<usize>,
+    // Factor --max-megapixels resized every sprite by before packing.
+    sprite_scale: Option<f32>,
+    // Per-frame flags for the fully transparent frames --shrink cropped off
+    // the sheet; their rect is empty. Otherwise empty.
+    cropped_frames: Vec<bool>,
+}
+
+impl Layout {
+    // The sheet's size in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    // The grid cell's size, before any --optimize-rows shortening.
+    pub fn cell_size(&self) -> (u32, u32) {
+        (self.cell_width, self.cell_height)
+    }
+
+    // Index of this sheet when the frames are split over several pages.
+    pub fn page(&self) -> Option<usize> {
+        self.page
+    }
+
+    // Every frame's cell as (x, y, width, height), in input order. These are
+    // the unscaled coordinates; see scale().
+    pub fn frame_rects(&self) -> Vec<(u32, u32, u32, u32)> {
+        (0..self.positions.len())
+            .map(|index| {
+                let (x, y) = self.positions[index];
+                let (width, height) = self.frame_size(index);
+                (x, y, width, height)
+            })
+            .collect()
+    }
+
+    // Factors the saved sheet was resized by, horizontally and vertically.
+    pub fn scale(&self) -> (f32, f32) {
+        self.scale
+    }
+
+    fn frame_size(&self, index: usize) -> (u32, u32) {
+        if self.is_cropped(index) {
+            return (0, 0);
+        }
+        let height = self.frame_heights.get(index).copied();
+        (self.cell_width, height.unwrap_or(self.cell_height))
+    }
+
+    fn is_cropped(&self, index: usize) -> bool {
+        self.cropped_frames.get(index) == Some(&true)
+    }
+}
+
+// What a registered atlas writer gets besides the layout: the name of the
+// image the rects refer to and the frames' atlas keys.
+pub struct SheetInfo {
+    image_name: String,
+    frame_names: Vec<String>,
+}
+
+impl SheetInfo {
+    pub fn image_name(&self) -> &str {
+        &self.image_name
+    }
+
+    // One key per frame rect, in input order, with --rename-pattern applied.
+    pub fn frame_names(&self) -> &[String] {
+        &self.frame_names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_cmp_orders_digit_runs_by_value() {
+        let mut names = vec![
+            "f10.png", "f2.png", "f1.png", "g.png", "f02b.png", "f2a.png",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["f1.png", "f2.png", "f2a.png", "f02b.png", "f10.png", "g.png"]
+        );
+    }
+
+    #[test]
+    fn natural_cmp_handles_padding_and_edge_cases() {
+        use std::cmp::Ordering;
+        // Same number, different padding: plain string order keeps it total.
+        assert_eq!(natural_cmp("f01", "f1"), "f01".cmp("f1"));
+        assert_eq!(natural_cmp("f1", "f01"), Ordering::Greater);
+        assert_eq!(natural_cmp("", ""), Ordering::Equal);
+        assert_eq!(natural_cmp("", "a"), Ordering::Less);
+        assert_eq!(natural_cmp("a1", "a"), Ordering::Greater);
+        // Numbers longer than any integer type still compare by value.
+        assert_eq!(
+            natural_cmp(
+                "x123456789012345678901234567890",
+                "x99999999999999999999999999999"
+            ),
+            Ordering::Greater
+        );
+        assert_eq!(natural_cmp("x007", "x7y"), Ordering::Less);
+    }
+
+    #[test]
+    fn registered_writers_get_the_layout_and_frame_names() {
+        let dir = std::env::temp_dir().join(format!("image_util_writer_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        for name in ["b.png", "a.png"] {
+            image::RgbaImage::new(4, 2)
+                .save(dir.join("in").join(name))
+                .unwrap();
+        }
+        let (input, output) = (dir.join("in"), dir.join("sheet.png"));
+        let mut options = PackOptions::new(&[
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--format",
+            "engine",
+            "--rename-pattern",
+            "{stem}_{index}",
+            "auto",
+        ]);
+        options.register_writer("engine", |layout, sheet| {
+            let mut lines = vec![format!(
+                "{} {:?} {:?} {:?}",
+                sheet.image_name(),
+                layout.size(),
+                layout.cell_size(),
+                layout.page()
+            )];
+            for (name, rect) in sheet.frame_names().iter().zip(layout.frame_rects()) {
+                lines.push(format!("{} {:?}", name, rect));
+            }
+            lines.join("\n")
+        });
+        let packed = options.pack().is_ok();
+        let atlas = std::fs::read_to_string(dir.join("sheet.engine"));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(packed);
+        assert_eq!(
+            atlas.unwrap(),
+            "sheet.png (4, 4) (4, 2) None\na_0 (0, 0, 4, 2)\nb_1 (0, 2, 4, 2)"
+        );
+    }
+}
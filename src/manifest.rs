@@ -0,0 +1,91 @@
+use crate::{get_image_format, http, sniff_format, warn, ImageData, Settings, SpritesheetErr};
+
+// Reads a frame list with one image per line, paths relative to the manifest.
+// A line may start with the frame index (`3 walk_3.png`); frames skipped by
+// the indices, and listed files that don't exist, become empty placeholder
+// cells so every other frame keeps its index. `http://` lines are downloaded;
+// `https://` lines fail before anything is fetched, since there's no TLS.
+pub fn read_frames(
+    path: &std::path::Path,
+    settings: &Settings,
+) -> Result<Vec<ImageData>, SpritesheetErr> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't read {}", path.display())))?;
+    let base_dir = path.parent().unwrap_or(std::path::Path::new(""));
+    if let Some(url) = contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .find(|word| word.starts_with("https://"))
+    {
+        return Err(SpritesheetErr::ImportError(format!(
+            "{} lists {}, but only http:// URLs are supported (no TLS implementation is bundled)",
+            path.display(),
+            url
+        )));
+    }
+
+    let mut images = Vec::new();
+    let mut missing_count = 0;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (index, name) = match line.split_once(char::is_whitespace) {
+            Some((index, name)) if index.parse::<usize>().is_ok() => {
+                (index.parse().unwrap(), name.trim())
+            }
+            _ => (images.len(), line),
+        };
+        if index < images.len() {
+            return Err(SpritesheetErr::ImportError(format!(
+                "frame index {} in {} is out of order",
+                index,
+                path.display()
+            )));
+        }
+        while images.len() < index {
+            images.push(ImageData::placeholder(format!("missing-{}", images.len())));
+            missing_count += 1;
+        }
+
+        if http::is_url(name) {
+            images.push(http::fetch_image(name)?);
+            continue;
+        }
+        let image_path = base_dir.join(name);
+        if !image_path.is_file() {
+            images.push(ImageData::placeholder(name.to_string()));
+            missing_count += 1;
+            continue;
+        }
+        let format = image_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(get_image_format)
+            .or_else(|| sniff_format(&image_path))
+            .ok_or_else(|| SpritesheetErr::ImportError(format!("unsupported image {}", name)))?;
+        images.push(ImageData {
+            name: name.to_string(),
+            path: image_path,
+            format,
+            duration: None,
+            bytes: None,
+            placeholder: false,
+            page_break: false,
+            layers: Vec::new(),
+        });
+    }
+
+    if missing_count > 0 {
+        let message = format!(
+            "{} manifest frames are missing, leaving their cells empty",
+            missing_count
+        );
+        warn(settings, message, None);
+    }
+    if images.iter().all(|image_info| image_info.placeholder) {
+        return Err(SpritesheetErr::NoImagesFound);
+    }
+    Ok(images)
+}
@@ -0,0 +1,112 @@
+use crate::SpritesheetErr;
+
+const KEYWORD: &str = "spritesheet-atlas";
+
+// Writes the sheet as a PNG, optionally carrying the JSON atlas in an iTXt
+// chunk so the image and its frame data travel as one file, and optionally as
+// an indexed image with a fixed palette (dithered with --dither). The encoder
+// settings are pinned and no time or text chunks are added besides the atlas,
+// so equal pixels always give equal bytes (--deterministic).
+pub fn save_png(
+    image: &image::DynamicImage,
+    path: &std::path::Path,
+    atlas: Option<String>,
+    palette: Option<&[[u8; 3]]>,
+    dither: bool,
+) -> Result<(), SpritesheetErr> {
+    let file = std::fs::File::create(path).map_err(|_| SpritesheetErr::ImageSaveError)?;
+    let mut encoder =
+        png::Encoder::new(std::io::BufWriter::new(file), image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_compression(png::Compression::Default);
+    encoder.set_filter(png::FilterType::Paeth);
+    encoder.set_adaptive_filter(png::AdaptiveFilterType::NonAdaptive);
+    let data = match (image, palette) {
+        (_, Some(palette)) => {
+            let (indices, transparent_index) =
+                crate::palette::quantize(&image.to_rgba8(), palette, dither)?;
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut colors: Vec<u8> = palette.iter().flatten().copied().collect();
+            if let Some(index) = transparent_index {
+                colors.extend_from_slice(&[0, 0, 0]);
+                let mut alphas = vec![255; index as usize];
+                alphas.push(0);
+                encoder.set_trns(alphas);
+            }
+            encoder.set_palette(colors);
+            indices
+        }
+        (image::DynamicImage::ImageRgba16(buffer), None) => {
+            encoder.set_depth(png::BitDepth::Sixteen);
+            buffer
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect()
+        }
+        (image::DynamicImage::ImageRgb16(_), None)
+        | (image::DynamicImage::ImageLuma16(_), None)
+        | (image::DynamicImage::ImageLumaA16(_), None) => {
+            encoder.set_color(png_color(image));
+            encoder.set_depth(png::BitDepth::Sixteen);
+            image
+                .as_bytes()
+                .chunks_exact(2)
+                .flat_map(|value| u16::from_ne_bytes([value[0], value[1]]).to_be_bytes())
+                .collect()
+        }
+        (image::DynamicImage::ImageLuma8(_), None)
+        | (image::DynamicImage::ImageLumaA8(_), None)
+        | (image::DynamicImage::ImageRgb8(_), None) => {
+            encoder.set_color(png_color(image));
+            encoder.set_depth(png::BitDepth::Eight);
+            image.as_bytes().to_vec()
+        }
+        (_, None) => {
+            encoder.set_depth(png::BitDepth::Eight);
+            image.to_rgba8().into_raw()
+        }
+    };
+    if let Some(atlas) = atlas {
+        encoder
+            .add_itxt_chunk(KEYWORD.to_string(), atlas)
+            .map_err(|_| SpritesheetErr::ImageSaveError)?;
+    }
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|_| SpritesheetErr::ImageSaveError)
+}
+
+// The PNG color type of a non-RGBA sheet from --sdf or --color-type.
+fn png_color(image: &image::DynamicImage) -> png::ColorType {
+    match image.color().channel_count() {
+        1 => png::ColorType::Grayscale,
+        2 => png::ColorType::GrayscaleAlpha,
+        3 => png::ColorType::Rgb,
+        _ => png::ColorType::Rgba,
+    }
+}
+
+pub fn extract_metadata(args: &[String]) -> Result<(), SpritesheetErr> {
+    let [path] = args else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: extract-metadata <sheet.png>".to_string(),
+        ));
+    };
+    let error = |message: &str| SpritesheetErr::ImportError(format!("{}: {}", path, message));
+    let file = std::fs::File::open(path).map_err(|_| error("can't read file"))?;
+    let reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .map_err(|_| error("not a png"))?;
+    let atlas = reader
+        .info()
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == KEYWORD)
+        .ok_or_else(|| error("no embedded atlas"))?
+        .get_text()
+        .map_err(|_| error("corrupt atlas chunk"))?;
+    println!("{}", atlas);
+    Ok(())
+}
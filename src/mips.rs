@@ -0,0 +1,45 @@
+use crate::{copy_sprite, is_high_bit_depth, resize_filter, Settings, Sprite};
+use image::DynamicImage;
+
+// Replaces every sprite with its mip chain: the full-size image on the left
+// and `levels` halved copies stacked top to bottom to its right. Each sprite
+// keeps the rects of its levels (full size first) relative to its own
+// corner, and the returned cell fits the largest chain.
+pub fn add_mips(
+    mut images: Vec<Sprite>,
+    levels: u32,
+    settings: &Settings,
+) -> (Vec<Sprite>, (u32, u32)) {
+    let filter = resize_filter(settings);
+    let mut cell = (0, 0);
+    for sprite in images.iter_mut().filter(|sprite| !sprite.placeholder) {
+        let (width, height) = (sprite.image.width(), sprite.image.height());
+        let mut rects = vec![(0, 0, width, height)];
+        let mut top = 0;
+        for level in 1..=levels {
+            let size = ((width >> level).max(1), (height >> level).max(1));
+            rects.push((width, top, size.0, size.1));
+            top += size.1;
+        }
+        let chain_width = width + rects.get(1).map_or(0, |rect| rect.2);
+        let chain_height = height.max(top);
+
+        let mut chain = if is_high_bit_depth(&sprite.image) {
+            DynamicImage::new_rgba16(chain_width, chain_height)
+        } else {
+            DynamicImage::new_rgba8(chain_width, chain_height)
+        };
+        for &(x, y, level_width, level_height) in rects.iter() {
+            let level = match (level_width, level_height) == (width, height) {
+                true => sprite.image.clone(),
+                false => sprite.image.resize_exact(level_width, level_height, filter),
+            };
+            copy_sprite(&mut chain, &level, (x, y));
+        }
+
+        sprite.image = chain;
+        sprite.mips = rects;
+        cell = (cell.0.max(chain_width), cell.1.max(chain_height));
+    }
+    (images, cell)
+}
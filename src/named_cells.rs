@@ -0,0 +1,115 @@
+use crate::{Layout, SpritesheetErr};
+
+enum Token<'a> {
+    Literal(&'a str),
+    Row,
+    Column,
+}
+
+// A pattern like `r{row}_c{col}` needs both placeholders exactly once and no
+// other braces.
+pub fn is_valid_pattern(pattern: &str) -> bool {
+    let Some(tokens) = tokenize(pattern) else {
+        return false;
+    };
+    let rows = tokens.iter().filter(|token| matches!(token, Token::Row));
+    let columns = tokens.iter().filter(|token| matches!(token, Token::Column));
+    rows.count() == 1 && columns.count() == 1
+}
+
+// Splits a pattern into literals and placeholders.
+fn tokenize(pattern: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            tokens.push(Token::Literal(&rest[..start]));
+        }
+        let end = start + rest[start..].find('}')? + 1;
+        tokens.push(match &rest[start..end] {
+            "{row}" => Token::Row,
+            "{col}" => Token::Column,
+            _ => return None,
+        });
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Literal(rest));
+    }
+    Some(tokens)
+}
+
+// Finds the pattern anywhere in the name and returns its (row, column).
+fn find_cell(tokens: &[Token], name: &str) -> Option<(u32, u32)> {
+    (0..name.len())
+        .filter(|&start| name.is_char_boundary(start))
+        .find_map(|start| match_cell(tokens, &name[start..]))
+}
+
+fn match_cell(tokens: &[Token], mut text: &str) -> Option<(u32, u32)> {
+    let (mut row, mut column) = (None, None);
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => text = text.strip_prefix(literal)?,
+            Token::Row | Token::Column => {
+                let digits = text.len()
+                    - text
+                        .trim_start_matches(|char: char| char.is_ascii_digit())
+                        .len();
+                let value = text[..digits].parse().ok()?;
+                text = &text[digits..];
+                if matches!(token, Token::Row) {
+                    row = Some(value);
+                } else {
+                    column = Some(value);
+                }
+            }
+        }
+    }
+    Some((row?, column?))
+}
+
+// Puts every frame at the grid cell its file name names instead of the next
+// free one. The grid is just large enough for the highest row and column, and
+// cells nobody names stay empty.
+pub fn layout(pattern: &str, names: &[&str], cell: (u32, u32)) -> Result<Layout, SpritesheetErr> {
+    let tokens = tokenize(pattern).unwrap();
+    let mut cells: Vec<(u32, u32)> = Vec::new();
+    for name in names {
+        let stem = std::path::Path::new(name).with_extension("");
+        let found = find_cell(&tokens, &stem.to_string_lossy()).ok_or_else(|| {
+            SpritesheetErr::InvalidSettings(format!(
+                "{} doesn't match the --cell-from-name pattern {}",
+                name, pattern
+            ))
+        })?;
+        if let Some(other) = cells.iter().position(|&taken| taken == found) {
+            return Err(SpritesheetErr::InvalidSettings(format!(
+                "{} and {} both name row {}, column {}",
+                names[other], name, found.0, found.1
+            )));
+        }
+        cells.push(found);
+    }
+
+    let columns = cells.iter().map(|cell| cell.1 + 1).max().unwrap_or(1);
+    let rows = cells.iter().map(|cell| cell.0 + 1).max().unwrap_or(0);
+    Ok(Layout {
+        columns,
+        rows,
+        cell_width: cell.0,
+        cell_height: cell.1,
+        width: columns * cell.0,
+        height: rows * cell.1,
+        positions: cells
+            .iter()
+            .map(|(row, column)| (column * cell.0, row * cell.1))
+            .collect(),
+        scale: (1.0, 1.0),
+        frame_heights: Vec::new(),
+        ring_angles: Vec::new(),
+        page: None,
+        sprite_scale: None,
+        cropped_frames: Vec::new(),
+    })
+}
@@ -0,0 +1,206 @@
+use crate::SpritesheetErr;
+
+// Reads a fixed palette from a GIMP `.gpl`, a JASC `.pal` or a plain list of
+// `RRGGBB` hex colors, one color per line.
+pub fn read_palette(path: &std::path::Path) -> Result<Vec<[u8; 3]>, SpritesheetErr> {
+    let error =
+        |message: &str| SpritesheetErr::ImportError(format!("{}: {}", path.display(), message));
+    let contents = std::fs::read_to_string(path).map_err(|_| error("can't read palette"))?;
+    let mut lines = contents.lines().map(str::trim).peekable();
+
+    let mut colors = Vec::new();
+    match lines.peek().copied() {
+        Some("GIMP Palette") => {
+            for line in lines.skip(1) {
+                if line.is_empty() || line.starts_with('#') || line.contains(':') {
+                    continue;
+                }
+                let channels: Vec<&str> = line.split_whitespace().take(3).collect();
+                colors.push(parse_channels(&channels).ok_or_else(|| error(line))?);
+            }
+        }
+        Some("JASC-PAL") => {
+            // Header, version and color count come before the colors.
+            for line in lines.skip(3) {
+                if line.is_empty() {
+                    continue;
+                }
+                let channels: Vec<&str> = line.split_whitespace().collect();
+                colors.push(parse_channels(&channels).ok_or_else(|| error(line))?);
+            }
+        }
+        _ => {
+            for line in lines {
+                if line.is_empty() || line.starts_with(';') {
+                    continue;
+                }
+                let color =
+                    crate::parse_color(line).filter(|_| line.trim_start_matches('#').len() == 6);
+                let color = color.ok_or_else(|| error(line))?;
+                colors.push([color[0], color[1], color[2]]);
+            }
+        }
+    }
+
+    if colors.is_empty() || colors.len() > 256 {
+        return Err(error("a palette needs 1 to 256 colors"));
+    }
+    Ok(colors)
+}
+
+fn parse_channels(channels: &[&str]) -> Option<[u8; 3]> {
+    match channels {
+        [red, green, blue] => Some([red.parse().ok()?, green.parse().ok()?, blue.parse().ok()?]),
+        _ => None,
+    }
+}
+
+// Maps every pixel to the palette index with the nearest RGB color. Pixels
+// with alpha below half become an extra fully transparent entry appended after
+// the palette, so the palette's own indices never move.
+pub fn quantize(
+    image: &image::RgbaImage,
+    palette: &[[u8; 3]],
+    dither: bool,
+) -> Result<(Vec<u8>, Option<u8>), SpritesheetErr> {
+    let has_transparency = image.pixels().any(|pixel| pixel[3] < 128);
+    let transparent_index = if has_transparency {
+        let index = u8::try_from(palette.len()).map_err(|_| {
+            SpritesheetErr::InvalidSettings(
+                "the sheet has transparent pixels, so the palette can have at most 255 colors"
+                    .to_string(),
+            )
+        })?;
+        Some(index)
+    } else {
+        None
+    };
+
+    if dither {
+        return Ok((
+            dither_floyd_steinberg(image, palette, transparent_index),
+            transparent_index,
+        ));
+    }
+
+    let mut cache = std::collections::HashMap::new();
+    let indices = image
+        .pixels()
+        .map(|pixel| {
+            if pixel[3] < 128 {
+                return transparent_index.unwrap();
+            }
+            let rgb = [pixel[0], pixel[1], pixel[2]];
+            *cache
+                .entry(rgb)
+                .or_insert_with(|| nearest_color(palette, rgb))
+        })
+        .collect();
+    Ok((indices, transparent_index))
+}
+
+// Floyd-Steinberg error diffusion: each pixel's rounding error is spread to the
+// unvisited neighbours (7/16 right, 3/16 down-left, 5/16 down, 1/16
+// down-right). Transparent pixels neither take nor pass on error, so sprites
+// don't bleed into empty cells.
+fn dither_floyd_steinberg(
+    image: &image::RgbaImage,
+    palette: &[[u8; 3]],
+    transparent_index: Option<u8>,
+) -> Vec<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut errors = vec![[0.0f32; 3]; width * height];
+    let mut indices = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x as u32, y as u32);
+            if pixel[3] < 128 {
+                indices.push(transparent_index.unwrap());
+                continue;
+            }
+            let wanted: [f32; 3] = std::array::from_fn(|channel| {
+                pixel[channel] as f32 + errors[y * width + x][channel]
+            });
+            let rgb = wanted.map(|value| value.round().clamp(0.0, 255.0) as u8);
+            let index = nearest_color(palette, rgb);
+            indices.push(index);
+
+            let chosen = palette[index as usize];
+            let error: [f32; 3] =
+                std::array::from_fn(|channel| wanted[channel] - chosen[channel] as f32);
+            for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || nx >= width as i64 || ny >= height as i64 {
+                    continue;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                if image.get_pixel(nx as u32, ny as u32)[3] < 128 {
+                    continue;
+                }
+                for channel in 0..3 {
+                    errors[ny * width + nx][channel] += error[channel] * weight / 16.0;
+                }
+            }
+        }
+    }
+    indices
+}
+
+fn nearest_color(palette: &[[u8; 3]], rgb: [u8; 3]) -> u8 {
+    let distance = |color: &[u8; 3]| {
+        (0..3)
+            .map(|channel| (color[channel] as i32 - rgb[channel] as i32).pow(2))
+            .sum::<i32>()
+    };
+    (0..palette.len())
+        .min_by_key(|index| distance(&palette[*index]))
+        .unwrap() as u8
+}
+
+// The colors of a reference image's visible pixels (alpha of at least half),
+// most used first. Only the 255 most used are kept, so remapping can still
+// set aside an index for transparency.
+pub fn read_image_palette(path: &std::path::Path) -> Result<Vec<[u8; 3]>, SpritesheetErr> {
+    let image = image::open(path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", path.display())))?;
+    let mut counts = std::collections::HashMap::new();
+    for pixel in image.to_rgba8().pixels().filter(|pixel| pixel[3] >= 128) {
+        *counts.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0u64) += 1;
+    }
+    let mut colors: Vec<([u8; 3], u64)> = counts.into_iter().collect();
+    colors.sort_by_key(|&(color, count)| (std::cmp::Reverse(count), color));
+    if colors.is_empty() {
+        return Err(SpritesheetErr::ImportError(format!(
+            "{} has no visible pixels to take a palette from",
+            path.display()
+        )));
+    }
+    Ok(colors
+        .into_iter()
+        .take(255)
+        .map(|(color, _)| color)
+        .collect())
+}
+
+// Replaces every pixel's color with the nearest palette color (dithered like
+// quantize with --dither), keeping its alpha.
+pub fn remap(
+    image: &mut image::RgbaImage,
+    palette: &[[u8; 3]],
+    dither: bool,
+) -> Result<(), SpritesheetErr> {
+    let (indices, _) = quantize(image, palette, dither)?;
+    for (pixel, index) in image.pixels_mut().zip(indices) {
+        if pixel[3] == 0 {
+            continue;
+        }
+        // Faint pixels got the transparent entry; they still take a color.
+        let index = match (index as usize) < palette.len() {
+            true => index,
+            false => nearest_color(palette, [pixel[0], pixel[1], pixel[2]]),
+        };
+        let [red, green, blue] = palette[index as usize];
+        *pixel = image::Rgba([red, green, blue, pixel[3]]);
+    }
+    Ok(())
+}
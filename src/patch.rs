@@ -0,0 +1,157 @@
+use crate::{
+    atlas::{frame_key, json_string},
+    calculate_layout, calculate_row_count, create_spritesheet, save_image, Layout, Settings,
+    Sprite, SpritesheetErr,
+};
+
+// FNV-1a over the frame size and pixels. Unlike std's hasher the result is
+// stable across builds, so it can be stored between runs.
+pub fn frame_hash(image: &image::DynamicImage) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let size = [image.width().to_le_bytes(), image.height().to_le_bytes()];
+    for byte in size.iter().flatten().chain(image.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn cache_path(image_path: &std::path::Path) -> std::path::PathBuf {
+    image_path.with_extension("hashes")
+}
+
+// Frame name to hash, as written by the last full run. None when there is no
+// cache yet.
+pub fn read_cache(
+    image_path: &std::path::Path,
+) -> Result<Option<Vec<(String, u64)>>, SpritesheetErr> {
+    let path = cache_path(image_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let error = || SpritesheetErr::ImportError(format!("can't read {}", path.display()));
+    let contents = std::fs::read_to_string(&path).map_err(|_| error())?;
+    let mut cache = Vec::new();
+    for line in contents.lines().filter(|line| !line.is_empty()) {
+        let (hash, name) = line.split_once(' ').ok_or_else(error)?;
+        let hash = u64::from_str_radix(hash, 16).map_err(|_| error())?;
+        cache.push((name.to_string(), hash));
+    }
+    Ok(Some(cache))
+}
+
+pub fn write_cache(images: &[Sprite], image_path: &std::path::Path) -> Result<(), SpritesheetErr> {
+    let contents: String = images
+        .iter()
+        .filter(|sprite| !sprite.placeholder)
+        .map(|sprite| format!("{:016x} {}\n", frame_hash(&sprite.image), sprite.name))
+        .collect();
+    std::fs::write(cache_path(image_path), contents).map_err(|_| SpritesheetErr::AtlasSaveError)
+}
+
+// Lists the frames added, removed and changed since the run that wrote the
+// cache (--changelog), or says that there is no earlier run to compare with.
+pub fn print_changelog(
+    images: &[Sprite],
+    cache: Option<&[(String, u64)]>,
+    image_path: &std::path::Path,
+) {
+    let Some(cache) = cache else {
+        println!("Changelog: no earlier build of {}", image_path.display());
+        return;
+    };
+    let frames: Vec<&Sprite> = images.iter().filter(|sprite| !sprite.placeholder).collect();
+    let mut lines = Vec::new();
+    for sprite in frames.iter() {
+        match cache.iter().find(|entry| entry.0 == sprite.name) {
+            None => lines.push(format!("  added    {}", sprite.name)),
+            Some(entry) if entry.1 != frame_hash(&sprite.image) => {
+                lines.push(format!("  changed  {}", sprite.name))
+            }
+            Some(_) => (),
+        }
+    }
+    for (name, _) in cache.iter() {
+        if !frames.iter().any(|sprite| sprite.name == *name) {
+            lines.push(format!("  removed  {}", name));
+        }
+    }
+    if lines.is_empty() {
+        println!("Changelog: no frames changed in {}", image_path.display());
+        return;
+    }
+    println!("Changelog for {}:", image_path.display());
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+// Packs the frames whose pixels differ from the cache (or that are new) into
+// `<name>_patch.png`, with `<name>_patch.json` mapping every patch rect to its
+// position in the main sheet. Returns the patch image's path, if one was
+// written.
+pub fn save_patch(
+    layout: &Layout,
+    images: Vec<Sprite>,
+    cache: &[(String, u64)],
+    image_path: &std::path::Path,
+    settings: &Settings,
+) -> Result<Vec<std::path::PathBuf>, SpritesheetErr> {
+    let mut targets = Vec::new();
+    let mut changed = Vec::new();
+    for (index, (sprite, position)) in images.into_iter().zip(layout.positions.iter()).enumerate() {
+        let hash = frame_hash(&sprite.image);
+        let cached = cache.iter().find(|entry| entry.0 == sprite.name);
+        if sprite.placeholder || cached.map(|entry| entry.1) == Some(hash) {
+            continue;
+        }
+        targets.push((index, *position));
+        changed.push(sprite);
+    }
+    if changed.is_empty() {
+        println!("No frames changed since the cached run");
+        return Ok(Vec::new());
+    }
+
+    let cell = (layout.cell_width, layout.cell_height);
+    let patch_layout = calculate_layout(
+        calculate_row_count(changed.len()).max(1),
+        changed.len(),
+        cell,
+        false,
+    );
+    let patch_sheet = create_spritesheet(&patch_layout, &changed, settings)?;
+
+    let stem = image_path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = image_path.extension().unwrap_or_default().to_string_lossy();
+    let patch_path = image_path.with_file_name(format!("{}_patch.{}", stem, extension));
+    save_image(patch_sheet, &patch_path, None, settings)?;
+
+    let frames: Vec<String> = changed
+        .iter()
+        .zip(patch_layout.positions.iter())
+        .zip(targets.iter())
+        .map(|((sprite, source), (index, target))| {
+            format!(
+                "    {{\"name\": {}, \"source\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}, \"target\": {{\"x\": {}, \"y\": {}}}}}",
+                json_string(&frame_key(&sprite.name, *index, settings)),
+                source.0,
+                source.1,
+                cell.0,
+                cell.1,
+                target.0,
+                target.1
+            )
+        })
+        .collect();
+    let descriptor = format!(
+        "{{\n  \"image\": {},\n  \"target\": {},\n  \"frames\": [\n{}\n  ]\n}}\n",
+        json_string(&patch_path.file_name().unwrap_or_default().to_string_lossy()),
+        json_string(&image_path.file_name().unwrap_or_default().to_string_lossy()),
+        frames.join(",\n")
+    );
+    std::fs::write(patch_path.with_extension("json"), descriptor)
+        .map_err(|_| SpritesheetErr::AtlasSaveError)?;
+    println!("Patched {} frames", changed.len());
+    Ok(vec![patch_path])
+}
@@ -0,0 +1,131 @@
+use image::{ImageBuffer, Rgba};
+
+// Spreads the color of opaque pixels into neighbouring fully transparent
+// pixels, one pixel deeper per pass. Alpha stays zero, so the sheet looks the
+// same but bilinear filtering no longer pulls in black from empty areas.
+pub fn bleed_alpha<T>(image: &mut ImageBuffer<Rgba<T>, Vec<T>>, passes: u32)
+where
+    T: image::Primitive + Into<u32> + TryFrom<u32>,
+    Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    let (width, height) = image.dimensions();
+    let mut filled: Vec<bool> = image.pixels().map(|pixel| pixel[3].into() > 0).collect();
+
+    for _ in 0..passes {
+        let mut updates = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                if filled[(y * width + x) as usize] {
+                    continue;
+                }
+
+                let mut sum = [0u32; 3];
+                let mut count = 0;
+                for (nx, ny) in neighbours(x, y, width, height) {
+                    if !filled[(ny * width + nx) as usize] {
+                        continue;
+                    }
+                    let pixel = image.get_pixel(nx, ny);
+                    for channel in 0..3 {
+                        sum[channel] += pixel[channel].into();
+                    }
+                    count += 1;
+                }
+
+                if count > 0 {
+                    updates.push((x, y, sum.map(|value| value / count)));
+                }
+            }
+        }
+
+        if updates.is_empty() {
+            break;
+        }
+        for (x, y, color) in updates {
+            let channel = |value: u32| T::try_from(value).unwrap_or(T::DEFAULT_MAX_VALUE);
+            let pixel = Rgba([
+                channel(color[0]),
+                channel(color[1]),
+                channel(color[2]),
+                channel(0),
+            ]);
+            image.put_pixel(x, y, pixel);
+            filled[(y * width + x) as usize] = true;
+        }
+    }
+}
+
+fn neighbours(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    let x_range = x.saturating_sub(1)..=(x + 1).min(width - 1);
+    let y_range = y.saturating_sub(1)..=(y + 1).min(height - 1);
+    y_range
+        .flat_map(move |ny| x_range.clone().map(move |nx| (nx, ny)))
+        .filter(move |&(nx, ny)| (nx, ny) != (x, y))
+}
+
+// Multiplies every color channel by the pixel's alpha, rounding to nearest.
+pub fn premultiply_alpha<T>(image: &mut ImageBuffer<Rgba<T>, Vec<T>>)
+where
+    T: image::Primitive + Into<u32> + TryFrom<u32>,
+    Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    let max: u32 = T::DEFAULT_MAX_VALUE.into();
+    for pixel in image.pixels_mut() {
+        let alpha: u32 = pixel[3].into();
+        for channel in 0..3 {
+            let value = (pixel[channel].into() * alpha + max / 2) / max;
+            pixel[channel] = T::try_from(value).unwrap_or(T::DEFAULT_MAX_VALUE);
+        }
+    }
+}
+
+// Composites every pixel over an opaque background color and makes it
+// opaque, for sheets saved without an alpha channel.
+pub fn flatten_alpha<T>(image: &mut ImageBuffer<Rgba<T>, Vec<T>>, background: [u8; 3])
+where
+    T: image::Primitive + Into<u32> + TryFrom<u32>,
+    Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    let max: u32 = T::DEFAULT_MAX_VALUE.into();
+    for pixel in image.pixels_mut() {
+        let alpha: u32 = pixel[3].into();
+        for channel in 0..3 {
+            let under = background[channel] as u32 * max / 255;
+            let value = (pixel[channel].into() * alpha + under * (max - alpha) + max / 2) / max;
+            pixel[channel] = T::try_from(value).unwrap_or(T::DEFAULT_MAX_VALUE);
+        }
+        pixel[3] = T::DEFAULT_MAX_VALUE;
+    }
+}
+
+// Whether some pixel has a color channel above its alpha, which can't happen
+// in a premultiplied image.
+pub fn has_color_above_alpha<T>(image: &ImageBuffer<Rgba<T>, Vec<T>>) -> bool
+where
+    T: image::Primitive,
+    Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    image
+        .pixels()
+        .any(|pixel| pixel.0[..3].iter().any(|&value| value > pixel.0[3]))
+}
+
+// Divides the color channels by alpha again, rounding to nearest. Fully
+// transparent pixels are left as they are.
+pub fn unpremultiply_alpha<T>(image: &mut ImageBuffer<Rgba<T>, Vec<T>>)
+where
+    T: image::Primitive + Into<u32> + TryFrom<u32>,
+    Rgba<T>: image::Pixel<Subpixel = T>,
+{
+    let max: u32 = T::DEFAULT_MAX_VALUE.into();
+    for pixel in image.pixels_mut() {
+        let alpha: u32 = pixel[3].into();
+        if alpha == 0 {
+            continue;
+        }
+        for channel in 0..3 {
+            let value = ((pixel[channel].into() * max + alpha / 2) / alpha).min(max);
+            pixel[channel] = T::try_from(value).unwrap_or(T::DEFAULT_MAX_VALUE);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::Layout;
+
+// Places the frames around `rings` concentric rings instead of a grid, for
+// --layout radial. Ring k (counting from 1 at the inside) holds a share of the
+// frames proportional to k, so the spacing along every ring is about the same.
+// The rings are evenly spaced, at least a cell diagonal apart, and far enough
+// out that neighbouring cells on a ring don't overlap. Frames go clockwise
+// from 12 o'clock, the inner ring first; their angles in degrees are kept in
+// `ring_angles` for the atlas.
+pub fn layout(count: usize, cell: (u32, u32), rings: u32) -> Layout {
+    let rings = (rings.max(1) as usize).min(count.max(1));
+    let total: usize = (1..=rings).sum();
+    let per_ring: Vec<usize> = (1..=rings)
+        .map(|ring| {
+            let before: usize = (1..ring).sum();
+            count * (before + ring) / total - count * before / total
+        })
+        .collect();
+
+    let diagonal = (cell.0 as f64).hypot(cell.1 as f64).max(1.0);
+    let spacing = per_ring
+        .iter()
+        .enumerate()
+        .filter(|(_, &frames)| frames > 1)
+        .map(|(index, &frames)| {
+            let radius = diagonal / (2.0 * (std::f64::consts::PI / frames as f64).sin());
+            radius / (index + 1) as f64
+        })
+        .fold(diagonal, f64::max);
+
+    let outer = spacing * rings as f64;
+    let width = (2.0 * outer).ceil() as u32 + cell.0;
+    let height = (2.0 * outer).ceil() as u32 + cell.1;
+    let center = (width as f64 / 2.0, height as f64 / 2.0);
+    let mut positions = Vec::with_capacity(count);
+    let mut ring_angles = Vec::with_capacity(count);
+    for (index, &frames) in per_ring.iter().enumerate() {
+        let radius = spacing * (index + 1) as f64;
+        for frame in 0..frames {
+            let angle = 360.0 * frame as f64 / frames as f64;
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let x = center.0 + radius * sin - cell.0 as f64 / 2.0;
+            let y = center.1 - radius * cos - cell.1 as f64 / 2.0;
+            positions.push((x.round().max(0.0) as u32, y.round().max(0.0) as u32));
+            ring_angles.push(angle as f32);
+        }
+    }
+
+    Layout {
+        columns: count as u32,
+        rows: 1,
+        cell_width: cell.0,
+        cell_height: cell.1,
+        width,
+        height,
+        positions,
+        scale: (1.0, 1.0),
+        frame_heights: Vec::new(),
+        ring_angles,
+        page: None,
+        sprite_scale: None,
+        cropped_frames: Vec::new(),
+    }
+}
@@ -0,0 +1,16 @@
+const MAGIC: [u8; 4] = *b"SPRT";
+const FORMAT_RGBA8: u32 = 0;
+
+// Writes the sheet as a `.raw`/`.bin` texture: a 16-byte header of the magic
+// `SPRT`, then width, height and format as little-endian u32s (format 0 is
+// RGBA8), followed by the rows top to bottom with no padding.
+pub fn encode(image: &image::RgbaImage) -> Vec<u8> {
+    let pixels = image.as_raw();
+    let mut file = Vec::with_capacity(16 + pixels.len());
+    file.extend_from_slice(&MAGIC);
+    for value in [image.width(), image.height(), FORMAT_RGBA8] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.extend_from_slice(pixels);
+    file
+}
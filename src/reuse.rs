@@ -0,0 +1,149 @@
+use crate::{
+    calculate_avg_color, collect_images, ImageData, Layout, Settings, Sprite, SpritesheetErr,
+};
+
+// Where a frame sat in the last sheet, and the size and modification time of
+// its source file then.
+struct CachedCell {
+    name: String,
+    rect: (u32, u32, u32, u32),
+    stamp: (u64, u128),
+}
+
+fn cells_path(image_path: &std::path::Path) -> std::path::PathBuf {
+    image_path.with_extension("cells")
+}
+
+fn source_stamp(image_info: &ImageData) -> Option<(u64, u128)> {
+    if image_info.placeholder || image_info.bytes.is_some() {
+        return None;
+    }
+    let metadata = std::fs::metadata(&image_info.path).ok()?;
+    let modified = metadata.modified().ok()?;
+    let modified = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+fn read_cells(image_path: &std::path::Path) -> Option<Vec<CachedCell>> {
+    let contents = std::fs::read_to_string(cells_path(image_path)).ok()?;
+    let mut cells = Vec::new();
+    for line in contents.lines().filter(|line| !line.is_empty()) {
+        let mut fields = line.splitn(7, ' ');
+        let mut number = || fields.next()?.parse::<u128>().ok();
+        let (Some(x), Some(y), Some(width), Some(height), Some(length), Some(modified)) =
+            (number(), number(), number(), number(), number(), number())
+        else {
+            return None;
+        };
+        cells.push(CachedCell {
+            name: fields.next()?.to_string(),
+            rect: (x as u32, y as u32, width as u32, height as u32),
+            stamp: (length as u64, modified),
+        });
+    }
+    Some(cells)
+}
+
+// Loads the sprites like collect_images, except that frames whose source file
+// kept its size and modification time since the last run are cut from the
+// previous sheet instead of being decoded again. Only the order of the frames
+// may have changed, since the sheet holds them untransformed.
+pub fn load_reusing_cells(
+    images_data: Vec<ImageData>,
+    image_path: &std::path::Path,
+    settings: &Settings,
+) -> Result<Vec<Sprite>, SpritesheetErr> {
+    let cells = read_cells(image_path).unwrap_or_default();
+    let sheet = match cells.is_empty() {
+        true => None,
+        false => image::open(image_path).ok(),
+    };
+    let Some(sheet) = sheet else {
+        return collect_images(images_data, settings);
+    };
+
+    let mut sprites: Vec<Option<Sprite>> = Vec::new();
+    let mut to_load = Vec::new();
+    for image_info in images_data {
+        let stamp = source_stamp(&image_info);
+        let cell = cells
+            .iter()
+            .find(|cell| cell.name == image_info.name && Some(cell.stamp) == stamp)
+            .filter(|cell| {
+                let (x, y, width, height) = cell.rect;
+                x + width <= sheet.width() && y + height <= sheet.height()
+            });
+        match cell {
+            Some(cell) => {
+                let (x, y, width, height) = cell.rect;
+                let image = sheet.crop_imm(x, y, width, height);
+                let avg_color = match settings.compute_avg_color
+                    || settings.sort.is_some_and(crate::SortOrder::needs_avg_color)
+                {
+                    true => calculate_avg_color(&image),
+                    false => None,
+                };
+                sprites.push(Some(Sprite {
+                    name: image_info.name,
+                    image,
+                    hash: None,
+                    avg_color,
+                    duration: image_info.duration,
+                    placeholder: false,
+                    trim: None,
+                    angle: 0.0,
+                    mips: Vec::new(),
+                    layers: Vec::new(),
+                }));
+            }
+            None => {
+                sprites.push(None);
+                to_load.push(image_info);
+            }
+        }
+    }
+
+    let reused = sprites.iter().filter(|sprite| sprite.is_some()).count();
+    let mut loaded = collect_images(to_load, settings)?.into_iter();
+    println!("Reused {} cells of {}", reused, image_path.display());
+    Ok(sprites
+        .into_iter()
+        .filter_map(|sprite| sprite.or_else(|| loaded.next()))
+        .collect())
+}
+
+// Records where every frame was placed, for the next run's
+// load_reusing_cells.
+pub fn write_cells(
+    layout: &Layout,
+    images: &[Sprite],
+    images_data: &[(String, Option<(u64, u128)>)],
+    image_path: &std::path::Path,
+) -> Result<(), SpritesheetErr> {
+    let mut contents = String::new();
+    for (index, sprite) in images.iter().enumerate().take(layout.positions.len()) {
+        let stamp = images_data
+            .iter()
+            .find(|(name, _)| *name == sprite.name)
+            .and_then(|(_, stamp)| *stamp);
+        let Some((length, modified)) = stamp else {
+            continue;
+        };
+        let (x, y) = layout.positions[index];
+        let (width, height) = layout.frame_size(index);
+        contents += &format!(
+            "{} {} {} {} {} {} {}\n",
+            x, y, width, height, length, modified, sprite.name
+        );
+    }
+    std::fs::write(cells_path(image_path), contents).map_err(|_| SpritesheetErr::AtlasSaveError)
+}
+
+// The source stamps of the frames, taken before loading so the cache matches
+// the files that were actually read.
+pub fn source_stamps(images_data: &[ImageData]) -> Vec<(String, Option<(u64, u128)>)> {
+    images_data
+        .iter()
+        .map(|image_info| (image_info.name.clone(), source_stamp(image_info)))
+        .collect()
+}
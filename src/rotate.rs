@@ -0,0 +1,83 @@
+use image::{DynamicImage, Rgba, Rgba32FImage};
+
+// Rotates the sprite clockwise by `degrees` onto a canvas grown to the rotated
+// bounding box, leaving the corners transparent. Quarter turns are exact,
+// other angles sample bilinearly (nearest neighbor with --pixel-art) with
+// premultiplied alpha, so transparent pixels don't darken the edges.
+pub fn rotate(image: &DynamicImage, degrees: f32, nearest: bool) -> DynamicImage {
+    let quarter_turns = degrees.rem_euclid(360.0) / 90.0;
+    if quarter_turns.fract() == 0.0 {
+        return match quarter_turns as u32 {
+            1 => image.rotate90(),
+            2 => image.rotate180(),
+            3 => image.rotate270(),
+            _ => image.clone(),
+        };
+    }
+
+    let source = image.to_rgba32f();
+    let (width, height) = (source.width() as f32, source.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    // Rounded first, so float noise doesn't add a whole row or column.
+    let extent = |value: f32| ((value * 1000.0).round() / 1000.0).ceil() as u32;
+    let rotated_width = extent(width * cos.abs() + height * sin.abs());
+    let rotated_height = extent(width * sin.abs() + height * cos.abs());
+
+    let sample = |x: i64, y: i64| -> [f32; 4] {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            return [0.0; 4];
+        }
+        let pixel = source.get_pixel(x as u32, y as u32).0;
+        [
+            pixel[0] * pixel[3],
+            pixel[1] * pixel[3],
+            pixel[2] * pixel[3],
+            pixel[3],
+        ]
+    };
+    let rotated = Rgba32FImage::from_fn(rotated_width, rotated_height, |x, y| {
+        let dx = x as f32 + 0.5 - rotated_width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - rotated_height as f32 / 2.0;
+        let source_x = cos * dx + sin * dy + width / 2.0 - 0.5;
+        let source_y = -sin * dx + cos * dy + height / 2.0 - 0.5;
+
+        let premultiplied = if nearest {
+            sample(source_x.round() as i64, source_y.round() as i64)
+        } else {
+            let (left, top) = (source_x.floor(), source_y.floor());
+            let (fx, fy) = (source_x - left, source_y - top);
+            let (left, top) = (left as i64, top as i64);
+            let corners = [
+                (sample(left, top), (1.0 - fx) * (1.0 - fy)),
+                (sample(left + 1, top), fx * (1.0 - fy)),
+                (sample(left, top + 1), (1.0 - fx) * fy),
+                (sample(left + 1, top + 1), fx * fy),
+            ];
+            let mut sum = [0.0; 4];
+            for (pixel, weight) in corners {
+                for channel in 0..4 {
+                    sum[channel] += pixel[channel] * weight;
+                }
+            }
+            sum
+        };
+
+        let alpha = premultiplied[3];
+        if alpha <= 0.0 {
+            return Rgba([0.0; 4]);
+        }
+        Rgba([
+            premultiplied[0] / alpha,
+            premultiplied[1] / alpha,
+            premultiplied[2] / alpha,
+            alpha,
+        ])
+    });
+
+    let rotated = DynamicImage::ImageRgba32F(rotated);
+    if crate::is_high_bit_depth(image) {
+        rotated.to_rgba16().into()
+    } else {
+        rotated.to_rgba8().into()
+    }
+}
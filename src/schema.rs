@@ -0,0 +1,179 @@
+use crate::SpritesheetErr;
+
+// JSON Schema of the json and json-array atlas formats, as atlas::write_json
+// writes them. Optional fields only appear with the options that add them.
+const ATLAS_SCHEMA: &str = r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "image_util JSON atlas",
+  "type": "object",
+  "required": ["frames", "meta"],
+  "additionalProperties": false,
+  "properties": {
+    "premultipliedAlpha": {"const": true},
+    "frames": {
+      "oneOf": [
+        {
+          "description": "--format json: frames keyed by name",
+          "type": "object",
+          "additionalProperties": {"$ref": "#/$defs/frame"}
+        },
+        {
+          "description": "--format json-array: frames in order with a filename",
+          "type": "array",
+          "items": {
+            "allOf": [{"$ref": "#/$defs/frame"}],
+            "required": ["filename"]
+          }
+        }
+      ]
+    },
+    "animations": {
+      "type": "object",
+      "additionalProperties": {"type": "array", "items": {"$ref": "#/$defs/index"}}
+    },
+    "meta": {
+      "type": "object",
+      "required": ["image", "format", "size", "scale"],
+      "additionalProperties": false,
+      "properties": {
+        "image": {"type": "string"},
+        "format": {
+          "enum": [
+            "RGBA8888",
+            "RGBA16161616",
+            "RGB888",
+            "RGB161616",
+            "Intensity",
+            "Intensity16",
+            "LuminanceAlpha",
+            "LuminanceAlpha1616"
+          ]
+        },
+        "size": {"$ref": "#/$defs/size"},
+        "page": {"$ref": "#/$defs/index"},
+        "layers": {"type": "object", "additionalProperties": {"type": "string"}},
+        "stagger": {
+          "type": "object",
+          "required": ["rows", "offset"],
+          "additionalProperties": false,
+          "properties": {
+            "rows": {"const": "odd"},
+            "offset": {"$ref": "#/$defs/index"}
+          }
+        },
+        "scale": {
+          "oneOf": [
+            {"type": "number"},
+            {
+              "type": "object",
+              "required": ["x", "y"],
+              "additionalProperties": false,
+              "properties": {"x": {"type": "number"}, "y": {"type": "number"}}
+            }
+          ]
+        },
+        "spriteScale": {"type": "number"},
+        "frameTags": {
+          "type": "array",
+          "items": {
+            "type": "object",
+            "required": ["name", "from", "to", "direction"],
+            "additionalProperties": false,
+            "properties": {
+              "name": {"type": "string"},
+              "from": {"$ref": "#/$defs/index"},
+              "to": {"$ref": "#/$defs/index"},
+              "direction": {"type": "string"}
+            }
+          }
+        },
+        "groups": {
+          "type": "array",
+          "items": {
+            "type": "object",
+            "required": ["name", "x", "y", "w", "h", "frames"],
+            "additionalProperties": false,
+            "properties": {
+              "name": {"type": "string"},
+              "x": {"type": "number"},
+              "y": {"type": "number"},
+              "w": {"type": "number"},
+              "h": {"type": "number"},
+              "frames": {"type": "array", "items": {"$ref": "#/$defs/index"}}
+            }
+          }
+        }
+      }
+    }
+  },
+  "$defs": {
+    "index": {"type": "integer", "minimum": 0},
+    "rect": {
+      "type": "object",
+      "required": ["x", "y", "w", "h"],
+      "additionalProperties": false,
+      "properties": {
+        "x": {"type": "number"},
+        "y": {"type": "number"},
+        "w": {"type": "number"},
+        "h": {"type": "number"}
+      }
+    },
+    "size": {
+      "type": "object",
+      "required": ["w", "h"],
+      "additionalProperties": false,
+      "properties": {"w": {"type": "number"}, "h": {"type": "number"}}
+    },
+    "frame": {
+      "type": "object",
+      "required": ["frame"],
+      "additionalProperties": false,
+      "properties": {
+        "filename": {"type": "string"},
+        "frame": {"$ref": "#/$defs/rect"},
+        "rotated": {"const": false},
+        "trimmed": {"const": true},
+        "spriteSourceSize": {"$ref": "#/$defs/rect"},
+        "sourceSize": {"$ref": "#/$defs/size"},
+        "avgColor": {"type": "string", "pattern": "^#[0-9A-F]{6}$"},
+        "duration": {"$ref": "#/$defs/index"},
+        "hash": {"type": "string"},
+        "angle": {"type": "number"},
+        "ring": {
+          "type": "object",
+          "required": ["center", "angle"],
+          "additionalProperties": false,
+          "properties": {
+            "center": {
+              "type": "object",
+              "required": ["x", "y"],
+              "additionalProperties": false,
+              "properties": {"x": {"type": "number"}, "y": {"type": "number"}}
+            },
+            "angle": {"type": "number"}
+          }
+        },
+        "opaqueBounds": {"$ref": "#/$defs/rect"},
+        "mips": {"type": "array", "items": {"$ref": "#/$defs/rect"}},
+        "pivot": {
+          "type": "object",
+          "required": ["x", "y"],
+          "additionalProperties": false,
+          "properties": {"x": {"type": "number"}, "y": {"type": "number"}}
+        }
+      },
+      "dependentRequired": {
+        "trimmed": ["spriteSourceSize", "sourceSize"]
+      }
+    }
+  }
+}"##;
+
+pub fn print_atlas_schema(args: &[String]) -> Result<(), SpritesheetErr> {
+    if let Some(arg) = args.first() {
+        return Err(SpritesheetErr::InvalidArgument(arg.clone()));
+    }
+    println!("{}", ATLAS_SCHEMA);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma};
+
+// Signed distance field of the sprite's shape (alpha of at least half): 128 on
+// the edge, brighter inside and darker outside, reaching 255 and 0 at `radius`
+// pixels from it. Farther distances are clamped, so only that window around
+// each pixel is searched. Everything beyond the image border is outside.
+pub fn distance_field(image: &DynamicImage, radius: u32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let inside: Vec<bool> = image
+        .pixels()
+        .map(|(_, _, pixel)| pixel[3] >= 128)
+        .collect();
+    let is_inside = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < width as i64
+            && y < height as i64
+            && inside[(y * width as i64 + x) as usize]
+    };
+
+    let window = radius as i64;
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let state = is_inside(x, y);
+        let mut nearest = (window * window) as f32;
+        for dy in -window..=window {
+            for dx in -window..=window {
+                if is_inside(x + dx, y + dy) != state {
+                    nearest = nearest.min((dx * dx + dy * dy) as f32);
+                }
+            }
+        }
+        // Pixel centers are half a pixel away from the edge between them.
+        let distance = (nearest.sqrt() - 0.5).max(0.0);
+        let signed = if state { distance } else { -distance };
+        let value = 128.0 + signed / radius as f32 * 127.0;
+        Luma([value.round().clamp(0.0, 255.0) as u8])
+    })
+}
@@ -0,0 +1,90 @@
+use crate::{
+    calculate_layout, create_spritesheet, parse_size, save_image, Settings, Sprite, SpritesheetErr,
+};
+use image::GenericImageView;
+
+// Every copy is held in memory until the sheet is drawn.
+const MAX_TILES: u32 = 65536;
+
+// Repeats one image across a CxR grid, so seams show up where the copies meet.
+pub fn tile_image(args: &[String]) -> Result<(), SpritesheetErr> {
+    let mut image_path = None;
+    let mut grid = None;
+    let mut output_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grid" => {
+                let value = args.next().ok_or(SpritesheetErr::ParseError)?;
+                grid = Some(
+                    parse_size(value)
+                        .ok_or_else(|| SpritesheetErr::InvalidArgument(value.clone()))?,
+                );
+            }
+            "--output" => output_path = args.next(),
+            _ if image_path.is_none() && !arg.starts_with("--") => image_path = Some(arg),
+            _ => return Err(SpritesheetErr::InvalidArgument(arg.clone())),
+        }
+    }
+    let (Some(image_path), Some((columns, rows))) = (image_path, grid) else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: tile <image> --grid CxR [--output file]".to_string(),
+        ));
+    };
+
+    let count = columns
+        .checked_mul(rows)
+        .filter(|count| (1..=MAX_TILES).contains(count))
+        .ok_or_else(|| {
+            SpritesheetErr::InvalidSettings(format!(
+                "--grid {}x{} must have between 1 and {} cells",
+                columns, rows, MAX_TILES
+            ))
+        })? as usize;
+    let image = image::open(image_path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", image_path)))?;
+    let (width, height) = image.dimensions();
+    if columns.checked_mul(width).is_none() || rows.checked_mul(height).is_none() {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "a {}x{} grid of {}x{} images is too large",
+            columns, rows, width, height
+        )));
+    }
+    let sprites: Vec<Sprite> = (0..count)
+        .map(|_| Sprite {
+            name: image_path.clone(),
+            image: image.clone(),
+            hash: None,
+            avg_color: None,
+            duration: None,
+            placeholder: false,
+            trim: None,
+            angle: 0.0,
+            mips: Vec::new(),
+            layers: Vec::new(),
+        })
+        .collect();
+
+    let settings = Settings::default();
+    let layout = calculate_layout(columns, count, image.dimensions(), false);
+    let sheet = create_spritesheet(&layout, &sprites, &settings)?;
+
+    let output_path = match output_path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let stem = std::path::Path::new(image_path)
+                .file_stem()
+                .unwrap_or_default();
+            std::path::PathBuf::from(format!("{}_tiled.png", stem.to_string_lossy()))
+        }
+    };
+    save_image(sheet, &output_path, None, &settings)?;
+    println!(
+        "Tiled {} {}x{} into {}",
+        image_path,
+        columns,
+        rows,
+        output_path.display()
+    );
+    Ok(())
+}
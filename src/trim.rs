@@ -0,0 +1,122 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::{Sprite, SpritesheetErr};
+
+// Bounding box (x, y, width, height) of the pixels that aren't fully
+// transparent, or None for an empty image.
+pub fn opaque_bounds(image: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    solid_bounds(image, 1)
+}
+
+// Bounding box of the pixels with at least `min_alpha` alpha, or None if
+// there are none.
+pub fn solid_bounds(image: &DynamicImage, min_alpha: u8) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in image.pixels() {
+        if pixel[3] < min_alpha {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((min_x, min_y, max_x, max_y)) => {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            }
+        });
+    }
+    bounds.map(|(min_x, min_y, max_x, max_y)| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
+
+// Where a trimmed sprite sat in its source image, in the TexturePacker sense:
+// `x` and `y` are the spriteSourceSize offset, the source size is the image
+// size before trimming.
+#[derive(Clone, Copy)]
+pub struct Trim {
+    pub x: u32,
+    pub y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+// Crops every sprite to its opaque bounds and returns the cell that fits the
+// largest of them. Fully transparent sprites keep a single pixel, like
+// TexturePacker does, so every frame still has a rect.
+pub fn trim_sprites(mut images: Vec<Sprite>) -> (Vec<Sprite>, (u32, u32)) {
+    let mut cell = (0, 0);
+    for sprite in images.iter_mut().filter(|sprite| !sprite.placeholder) {
+        let (x, y, width, height) = opaque_bounds(&sprite.image).unwrap_or((0, 0, 1, 1));
+        sprite.trim = Some(Trim {
+            x,
+            y,
+            source_width: sprite.image.width(),
+            source_height: sprite.image.height(),
+        });
+        sprite.image = sprite.image.crop_imm(x, y, width, height);
+        cell = (cell.0.max(width), cell.1.max(height));
+    }
+    (images, cell)
+}
+
+// Saves every trimmed sprite as a PNG in `dir`, named after its source file
+// with the extension replaced. Folder names from --folder-groups become
+// subdirectories.
+pub fn export_sprites(images: &[Sprite], dir: &std::path::Path) -> Result<(), SpritesheetErr> {
+    let sprites: Vec<&Sprite> = images.iter().filter(|sprite| !sprite.placeholder).collect();
+    for sprite in sprites.iter() {
+        let path = dir.join(&sprite.name).with_extension("png");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|_| SpritesheetErr::ImageSaveError)?;
+        }
+        sprite.image.save(&path)?;
+    }
+    println!(
+        "Exported {} trimmed sprites to {}",
+        sprites.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+// Lists how much transparent border each sprite has, largest savings first,
+// to point at source images exported with too much empty space.
+pub fn print_report(images: &[Sprite]) {
+    let mut rows = Vec::new();
+    for sprite in images.iter().filter(|sprite| !sprite.placeholder) {
+        let (width, height) = sprite.image.dimensions();
+        let area = width as u64 * height as u64;
+        let bounds = opaque_bounds(&sprite.image).unwrap_or((0, 0, 0, 0));
+        let saved = area - bounds.2 as u64 * bounds.3 as u64;
+        rows.push((saved, area, sprite, bounds));
+    }
+    rows.sort_by_key(|row| std::cmp::Reverse(row.0));
+
+    println!("Trim report:");
+    for (saved, area, sprite, (x, y, width, height)) in rows.iter() {
+        println!(
+            "  {}: {}x{} -> {}x{} at ({}, {}), saves {} px ({:.1}%)",
+            sprite.name,
+            sprite.image.width(),
+            sprite.image.height(),
+            width,
+            height,
+            x,
+            y,
+            saved,
+            percent(*saved, *area)
+        );
+    }
+    let saved: u64 = rows.iter().map(|row| row.0).sum();
+    let area: u64 = rows.iter().map(|row| row.1).sum();
+    println!(
+        "Total: trimming saves {} of {} px ({:.1}%)",
+        saved,
+        area,
+        percent(saved, area)
+    );
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
@@ -0,0 +1,162 @@
+use image::GenericImageView;
+
+use crate::{parse_size, SpritesheetErr};
+
+// How the cells lie along one axis of a sheet: `count` cells of `cell` pixels,
+// `gap` pixels apart, starting `margin` pixels in.
+struct Axis {
+    count: u32,
+    cell: u32,
+    gap: u32,
+    margin: u32,
+}
+
+// Cuts a sheet back into one PNG per non-empty cell. The grid comes from
+// --grid CxR, or --auto-grid finds it from the transparent gutters between
+// the cells.
+pub fn unpack_sheet(args: &[String]) -> Result<(), SpritesheetErr> {
+    let mut sheet_path = None;
+    let mut grid = None;
+    let mut auto_grid = false;
+    let mut output_dir = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grid" => {
+                let value = args.next().ok_or(SpritesheetErr::ParseError)?;
+                grid = Some(
+                    parse_size(value)
+                        .ok_or_else(|| SpritesheetErr::InvalidArgument(value.clone()))?,
+                );
+            }
+            "--auto-grid" => auto_grid = true,
+            "--output" => output_dir = args.next(),
+            _ if sheet_path.is_none() && !arg.starts_with("--") => sheet_path = Some(arg),
+            _ => return Err(SpritesheetErr::InvalidArgument(arg.clone())),
+        }
+    }
+    let Some(sheet_path) = sheet_path.filter(|_| grid.is_some() != auto_grid) else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: unpack <sheet> (--grid CxR | --auto-grid) [--output dir]".to_string(),
+        ));
+    };
+
+    let sheet = image::open(sheet_path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", sheet_path)))?;
+    let (columns, rows) = match grid {
+        Some((columns, rows)) => (
+            even_axis(sheet.width(), columns),
+            even_axis(sheet.height(), rows),
+        ),
+        None => {
+            let alpha = sheet.to_rgba8();
+            let empty_columns: Vec<bool> = (0..sheet.width())
+                .map(|x| (0..sheet.height()).all(|y| alpha.get_pixel(x, y)[3] == 0))
+                .collect();
+            let empty_rows: Vec<bool> = (0..sheet.height())
+                .map(|y| (0..sheet.width()).all(|x| alpha.get_pixel(x, y)[3] == 0))
+                .collect();
+            let (columns, rows) = (detect_axis(&empty_columns), detect_axis(&empty_rows));
+            println!(
+                "Detected a {}x{} grid of {}x{} cells, {}x{} px apart",
+                columns.count, rows.count, columns.cell, rows.cell, columns.gap, rows.gap
+            );
+            (columns, rows)
+        }
+    };
+    if columns.cell == 0 || rows.cell == 0 {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "the {}x{} sheet is smaller than the grid",
+            sheet.width(),
+            sheet.height()
+        )));
+    }
+
+    let stem = std::path::Path::new(sheet_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let output_dir = match output_dir {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => std::path::PathBuf::from(format!("{}_frames", stem)),
+    };
+    std::fs::create_dir_all(&output_dir).map_err(|_| SpritesheetErr::ImageSaveError)?;
+    let digits = (columns.count * rows.count)
+        .saturating_sub(1)
+        .to_string()
+        .len();
+    let mut saved = 0;
+    for row in 0..rows.count {
+        for column in 0..columns.count {
+            let x = columns.margin + column * (columns.cell + columns.gap);
+            let y = rows.margin + row * (rows.cell + rows.gap);
+            let frame = sheet.crop_imm(x, y, columns.cell, rows.cell);
+            // Unfilled cells at the end of the grid hold nothing.
+            if frame.pixels().all(|(_, _, pixel)| pixel[3] == 0) {
+                continue;
+            }
+            let index = row * columns.count + column;
+            let name = format!("{}_{:0width$}.png", stem, index, width = digits);
+            frame.save(output_dir.join(name))?;
+            saved += 1;
+        }
+    }
+    println!("Unpacked {} frames into {}", saved, output_dir.display());
+    Ok(())
+}
+
+fn even_axis(length: u32, count: u32) -> Axis {
+    Axis {
+        count,
+        cell: length / count,
+        gap: 0,
+        margin: 0,
+    }
+}
+
+// Finds the most cells of equal size whose gutters, and margins as wide as
+// the gutters if the sheet has them, are lines that are fully transparent
+// (`empty`). Transparent sprite edges look just like gutters, so of the
+// grids that fit, the one with the largest cells wins; every sprite lies
+// whole inside its cell either way. There are never more cells than runs of
+// non-empty lines, so sprites aren't split at their own transparent lines
+// unless they line up with a regular grid. Without gutters the whole axis is
+// one cell.
+fn detect_axis(empty: &[bool]) -> Axis {
+    let length = empty.len() as u32;
+    let runs = (0..empty.len())
+        .filter(|&line| !empty[line] && (line == 0 || empty[line - 1]))
+        .count() as u32;
+    for count in (2..=runs).rev() {
+        let mut best: Option<Axis> = None;
+        for gap in 1..length {
+            for margin in [0, gap] {
+                let used = (count - 1) * gap + 2 * margin;
+                if used >= length || !(length - used).is_multiple_of(count) {
+                    continue;
+                }
+                let cell = (length - used) / count;
+                let is_gutter = |line: u32| {
+                    line < margin
+                        || line >= length - margin
+                        || (line - margin) % (cell + gap) >= cell
+                };
+                let fits = (0..length)
+                    .filter(|&line| is_gutter(line))
+                    .all(|line| empty[line as usize]);
+                if fits && best.as_ref().is_none_or(|best| cell > best.cell) {
+                    best = Some(Axis {
+                        count,
+                        cell,
+                        gap,
+                        margin,
+                    });
+                }
+            }
+        }
+        if let Some(best) = best {
+            return best;
+        }
+    }
+    even_axis(length, 1)
+}
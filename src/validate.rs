@@ -0,0 +1,153 @@
+use crate::{json, SpritesheetErr};
+
+struct Rect {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    // Edges are u64 so hand-edited atlases near u32::MAX can't overflow.
+    fn right(&self) -> u64 {
+        self.x as u64 + self.width as u64
+    }
+
+    fn bottom(&self) -> u64 {
+        self.y as u64 + self.height as u64
+    }
+
+    fn overlaps(&self, other: &Rect) -> bool {
+        (self.x as u64) < other.right()
+            && (other.x as u64) < self.right()
+            && (self.y as u64) < other.bottom()
+            && (other.y as u64) < self.bottom()
+    }
+}
+
+pub fn validate_atlas(args: &[String]) -> Result<(), SpritesheetErr> {
+    let mut sheet_path = None;
+    let mut atlas_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sheet" => sheet_path = args.next(),
+            "--atlas" => atlas_path = args.next(),
+            _ => return Err(SpritesheetErr::InvalidArgument(arg.clone())),
+        }
+    }
+    let (Some(sheet_path), Some(atlas_path)) = (sheet_path, atlas_path) else {
+        return Err(SpritesheetErr::InvalidSettings(
+            "usage: validate --sheet <sheet.png> --atlas <atlas.json>".to_string(),
+        ));
+    };
+
+    let (width, height) = image::image_dimensions(sheet_path)
+        .map_err(|_| SpritesheetErr::ImportError(format!("can't open {}", sheet_path)))?;
+    let rects = read_rects(atlas_path)?;
+
+    let mut violations = 0;
+    for (index, rect) in rects.iter().enumerate() {
+        if rect.right() > width as u64 || rect.bottom() > height as u64 {
+            println!(
+                "{} ({}, {}, {}x{}) is outside the {}x{} sheet",
+                rect.name, rect.x, rect.y, rect.width, rect.height, width, height
+            );
+            violations += 1;
+        }
+        for other in rects[index + 1..].iter() {
+            if rect.overlaps(other) {
+                println!("{} overlaps {}", rect.name, other.name);
+                violations += 1;
+            }
+        }
+    }
+
+    if violations > 0 {
+        return Err(SpritesheetErr::ValidationFailed(violations));
+    }
+    println!("Atlas is valid: {} frames", rects.len());
+    Ok(())
+}
+
+fn read_rects(path: &str) -> Result<Vec<Rect>, SpritesheetErr> {
+    let error = |message: &str| SpritesheetErr::ImportError(format!("{}: {}", path, message));
+    let contents = std::fs::read_to_string(path).map_err(|_| error("can't read atlas"))?;
+    let atlas = json::parse(&contents).ok_or_else(|| error("invalid json"))?;
+
+    let mut frames = Vec::new();
+    match atlas.get("frames") {
+        Some(json::JsonValue::Object(entries)) => {
+            for (name, frame) in entries {
+                frames.push((name.clone(), frame));
+            }
+        }
+        Some(json::JsonValue::Array(values)) => {
+            for (index, frame) in values.iter().enumerate() {
+                let name = frame.get("filename").and_then(|value| value.as_str());
+                frames.push((name.map_or(index.to_string(), String::from), frame));
+            }
+        }
+        _ => return Err(error("no frames")),
+    }
+
+    let mut coords = Vec::new();
+    for (name, frame) in frames {
+        let rect = frame.get("frame");
+        let field = |key: &str| {
+            rect.and_then(|rect| rect.get(key))
+                .and_then(|value| value.as_f64())
+                .filter(|value| *value >= 0.0)
+        };
+        let (Some(x), Some(y), Some(width), Some(height)) =
+            (field("x"), field("y"), field("w"), field("h"))
+        else {
+            return Err(error(&format!("frame {} has no valid rect", name)));
+        };
+        coords.push((name, [x, y, width, height]));
+    }
+
+    // Atlases written with --coords normalized hold fractions of the sheet
+    // size, which are scaled back to pixels by meta.size.
+    let normalized = coords
+        .iter()
+        .any(|(_, values)| values.iter().any(|value| value.fract() != 0.0));
+    let scale = match normalized {
+        true => {
+            let size = atlas.get("meta").and_then(|meta| meta.get("size"));
+            let dimension = |key: &str| {
+                size.and_then(|size| size.get(key))
+                    .and_then(|value| value.as_u32())
+            };
+            let (Some(width), Some(height)) = (dimension("w"), dimension("h")) else {
+                return Err(error(
+                    "frame rects aren't whole pixels and there is no meta.size to scale them by",
+                ));
+            };
+            [width, height, width, height].map(|value| value as f64)
+        }
+        false => [1.0; 4],
+    };
+
+    let mut rects = Vec::new();
+    for (name, values) in coords {
+        let mut pixels = [0; 4];
+        for (index, value) in values.into_iter().enumerate() {
+            let value = (value * scale[index]).round();
+            if value > u32::MAX as f64 {
+                return Err(error(&format!("frame {} has no valid rect", name)));
+            }
+            pixels[index] = value as u32;
+        }
+        let [x, y, width, height] = pixels;
+        rects.push(Rect {
+            name,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+    Ok(rects)
+}
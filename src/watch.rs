@@ -0,0 +1,97 @@
+use std::hash::{Hash, Hasher};
+
+use crate::{find_sources, output_path, pack, print_error, ImageData, Settings, SpritesheetErr};
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+const DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(500);
+
+// Polls the sources every POLL_INTERVAL and repacks once they stop changing
+// for DEBOUNCE. A pack only runs when the file contents differ from the last
+// pack, so touching or rewriting a file with the same bytes doesn't trigger
+// any work. The images a pack writes (the sheet with its pages, scales,
+// layers, thumbnail, alpha mask or patch) are left out, so packing into an
+// input folder doesn't set off another pack.
+pub fn watch(settings: &Settings) -> Result<(), SpritesheetErr> {
+    let mut last_snapshot = None;
+    let mut last_change = std::time::Instant::now();
+    let mut pending = true;
+    let mut packed_checksum = None;
+    let mut outputs = vec![canonical(&output_path(settings))];
+
+    println!("Watching for changes, press Ctrl+C to stop");
+    loop {
+        let snapshot = take_snapshot(settings, &outputs);
+        if last_snapshot.as_ref() != Some(&snapshot) {
+            last_snapshot = Some(snapshot);
+            last_change = std::time::Instant::now();
+            pending = true;
+        } else if pending && last_change.elapsed() >= DEBOUNCE {
+            pending = false;
+            let checksum = calculate_checksum(settings, &outputs);
+            if packed_checksum != Some(checksum) {
+                packed_checksum = Some(checksum);
+                match pack(settings) {
+                    Ok(written) => {
+                        println!("Packed {}", output_path(settings).display());
+                        outputs.extend(written.iter().map(|path| canonical(path)));
+                        outputs.sort();
+                        outputs.dedup();
+                    }
+                    Err(error) => print_error(&error),
+                }
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// Cheap per-file state used to notice changes: size and modification time
+// for files on disk, the extracted size for archive entries.
+fn take_snapshot(
+    settings: &Settings,
+    outputs: &[std::path::PathBuf],
+) -> Vec<(std::path::PathBuf, u64, Option<std::time::SystemTime>)> {
+    inputs(settings, outputs)
+        .into_iter()
+        .map(|image_info| match &image_info.bytes {
+            Some(bytes) => (image_info.path, bytes.len() as u64, None),
+            None => {
+                let metadata = std::fs::metadata(&image_info.path).ok();
+                let len = metadata
+                    .as_ref()
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+                let modified = metadata.and_then(|metadata| metadata.modified().ok());
+                (image_info.path, len, modified)
+            }
+        })
+        .collect()
+}
+
+fn calculate_checksum(settings: &Settings, outputs: &[std::path::PathBuf]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for image_info in inputs(settings, outputs) {
+        image_info.path.hash(&mut hasher);
+        match &image_info.bytes {
+            Some(bytes) => bytes.hash(&mut hasher),
+            None => std::fs::read(&image_info.path).ok().hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+// The sources, without the images an earlier pack wrote.
+fn inputs(settings: &Settings, outputs: &[std::path::PathBuf]) -> Vec<ImageData> {
+    let Ok((images, _)) = find_sources(settings, &mut Vec::new()) else {
+        return Vec::new();
+    };
+    images
+        .into_iter()
+        .filter(|image_info| outputs.binary_search(&canonical(&image_info.path)).is_err())
+        .collect()
+}
+
+// Relative inputs and absolute output paths name the same file the same way.
+fn canonical(path: &std::path::Path) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,679 @@
+use crate::SpritesheetErr;
+
+const MAX_DIMENSION: u32 = 16384;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+const CODE_LENGTH_CODE_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+const LENGTH_PREFIX_CODES: usize = 24;
+const GREEN_ALPHABET_SIZE: usize = 256 + LENGTH_PREFIX_CODES;
+const DISTANCE_ALPHABET_SIZE: usize = 40;
+
+const PREDICTOR_TRANSFORM: u32 = 0;
+const SUBTRACT_GREEN_TRANSFORM: u32 = 2;
+// Each 16x16 block picks its own predictor.
+const PREDICTOR_BITS: u32 = 4;
+const PREDICTOR_MODES: u32 = 14;
+
+const HASH_BITS: u32 = 16;
+const MAX_CHAIN_LENGTH: usize = 32;
+const MIN_COPY_LENGTH: usize = 3;
+const MAX_COPY_LENGTH: usize = 4096;
+const MAX_COPY_DISTANCE: usize = (1 << 20) - 120;
+
+// The (x, y) offsets the first 120 distance codes stand for; larger codes are
+// plain distances plus 120.
+#[rustfmt::skip]
+const DISTANCE_MAP: [(i8, i8); 120] = [
+    (0, 1),  (1, 0),  (1, 1),  (-1, 1), (0, 2),  (2, 0),  (1, 2),  (-1, 2),
+    (2, 1),  (-2, 1), (2, 2),  (-2, 2), (0, 3),  (3, 0),  (1, 3),  (-1, 3),
+    (3, 1),  (-3, 1), (2, 3),  (-2, 3), (3, 2),  (-3, 2), (0, 4),  (4, 0),
+    (1, 4),  (-1, 4), (4, 1),  (-4, 1), (3, 3),  (-3, 3), (2, 4),  (-2, 4),
+    (4, 2),  (-4, 2), (0, 5),  (3, 4),  (-3, 4), (4, 3),  (-4, 3), (5, 0),
+    (1, 5),  (-1, 5), (5, 1),  (-5, 1), (2, 5),  (-2, 5), (5, 2),  (-5, 2),
+    (4, 4),  (-4, 4), (3, 5),  (-3, 5), (5, 3),  (-5, 3), (0, 6),  (6, 0),
+    (1, 6),  (-1, 6), (6, 1),  (-6, 1), (2, 6),  (-2, 6), (6, 2),  (-6, 2),
+    (4, 5),  (-4, 5), (5, 4),  (-5, 4), (3, 6),  (-3, 6), (6, 3),  (-6, 3),
+    (0, 7),  (7, 0),  (1, 7),  (-1, 7), (5, 5),  (-5, 5), (7, 1),  (-7, 1),
+    (4, 6),  (-4, 6), (6, 4),  (-6, 4), (2, 7),  (-2, 7), (7, 2),  (-7, 2),
+    (3, 7),  (-3, 7), (7, 3),  (-7, 3), (5, 6),  (-5, 6), (6, 5),  (-6, 5),
+    (8, 0),  (4, 7),  (-4, 7), (7, 4),  (-7, 4), (8, 1),  (8, 2),  (6, 6),
+    (-6, 6), (8, 3),  (5, 7),  (-5, 7), (7, 5),  (-7, 5), (8, 4),  (6, 7),
+    (-6, 7), (7, 6),  (-7, 6), (8, 5),  (7, 7),  (-7, 7), (8, 6),  (8, 7),
+];
+
+// Encodes the image as a VP8L WebP. The subtract-green and predictor
+// transforms turn the pixels into small residuals, which are then written
+// as Huffman-coded literals and LZ77 backward references.
+//
+// Without a near-lossless level the output is lossless. A level below 100
+// rounds the color residuals to coarser steps (near-lossless, as libwebp
+// calls it), so colors may drift by up to one step while alpha is always
+// kept exact.
+pub fn encode(
+    image: &image::RgbaImage,
+    near_lossless: Option<u8>,
+) -> Result<Vec<u8>, SpritesheetErr> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Err(SpritesheetErr::InvalidSettings(
+            "WebP output needs at least one pixel".to_string(),
+        ));
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(SpritesheetErr::InvalidSettings(format!(
+            "WebP output is limited to {0}x{0} pixels",
+            MAX_DIMENSION
+        )));
+    }
+
+    let mut pixels: Vec<u32> = image
+        .pixels()
+        .map(|pixel| {
+            let [red, green, blue, alpha] = pixel.0.map(|channel| channel as u32);
+            alpha << 24 | red << 16 | green << 8 | blue
+        })
+        .collect();
+    let uses_alpha = pixels.iter().any(|pixel| pixel >> 24 != 0xff);
+    let (width, height) = (width as usize, height as usize);
+
+    let mut writer = BitWriter::default();
+    writer.write(0x2f, 8);
+    writer.write(width as u32 - 1, 14);
+    writer.write(height as u32 - 1, 14);
+    writer.write(uses_alpha as u32, 1);
+    writer.write(0, 3);
+
+    // Quality 100 keeps every bit, 0 drops the low 5 bits of each residual.
+    let step = 1 << near_lossless.map_or(0, |level| (100 - level as u32) / 20);
+
+    // The decoder undoes the transforms in the reverse of the order they
+    // are written in. Rounded residuals need the real channels to clamp
+    // against, so near-lossless output skips subtract-green.
+    if step == 1 {
+        writer.write(1, 1);
+        writer.write(SUBTRACT_GREEN_TRANSFORM, 2);
+        subtract_green(&mut pixels);
+    }
+
+    writer.write(1, 1);
+    writer.write(PREDICTOR_TRANSFORM, 2);
+    writer.write(PREDICTOR_BITS - 2, 3);
+    let modes = predict(&mut pixels, width, height, step);
+    write_image(
+        &mut writer,
+        &modes,
+        width.div_ceil(1 << PREDICTOR_BITS),
+        false,
+    );
+    writer.write(0, 1);
+
+    write_image(&mut writer, &pixels, width, true);
+
+    let data = writer.finish();
+    let padding = data.len() % 2;
+    let mut webp = Vec::with_capacity(data.len() + 20 + padding);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&((12 + data.len() + padding) as u32).to_le_bytes());
+    webp.extend_from_slice(b"WEBPVP8L");
+    webp.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    webp.extend_from_slice(&data);
+    if padding == 1 {
+        webp.push(0);
+    }
+    Ok(webp)
+}
+
+fn subtract_green(pixels: &mut [u32]) {
+    for pixel in pixels.iter_mut() {
+        let green = (*pixel >> 8) & 0xff;
+        let red = ((*pixel >> 16).wrapping_sub(green)) & 0xff;
+        let blue = (*pixel).wrapping_sub(green) & 0xff;
+        *pixel = (*pixel & 0xff00ff00) | red << 16 | blue;
+    }
+}
+
+// Replaces every pixel with its difference from the prediction of the mode
+// that fits its block best, and returns the per-block modes as the
+// transform's sub-image. Color residuals are rounded to multiples of `step`,
+// and later pixels are predicted from what the decoder will reconstruct.
+fn predict(pixels: &mut [u32], width: usize, height: usize, step: i32) -> Vec<u32> {
+    let block_size = 1 << PREDICTOR_BITS;
+    let blocks_wide = width.div_ceil(block_size);
+    let blocks_high = height.div_ceil(block_size);
+    let original = pixels.to_vec();
+
+    let mut modes = Vec::with_capacity(blocks_wide * blocks_high);
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let xs = block_x * block_size..(block_x * block_size + block_size).min(width);
+            let ys = block_y * block_size..(block_y * block_size + block_size).min(height);
+            let mode = (0..PREDICTOR_MODES)
+                .min_by_key(|mode| {
+                    let mut cost = 0;
+                    for y in ys.clone() {
+                        for x in xs.clone() {
+                            let prediction = prediction(&original, width, x, y, *mode);
+                            cost += residual_cost(sub_pixels(original[y * width + x], prediction));
+                        }
+                    }
+                    cost
+                })
+                .unwrap_or(0);
+            modes.push(0xff000000 | mode << 8);
+        }
+    }
+
+    let mut reconstructed = original.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let mode = (modes[(y / block_size) * blocks_wide + x / block_size] >> 8) & 0xff;
+            let index = y * width + x;
+            let prediction = prediction(&reconstructed, width, x, y, mode);
+            if step == 1 {
+                pixels[index] = sub_pixels(original[index], prediction);
+                continue;
+            }
+
+            let mut residual = 0;
+            for channel in 0..4 {
+                let predicted = channel_of(prediction, channel);
+                let difference = channel_of(original[index], channel) - predicted;
+                let mut rounded = match channel {
+                    3 => difference,
+                    _ => (difference + step / 2).div_euclid(step) * step,
+                };
+                if predicted + rounded > 255 {
+                    rounded -= step;
+                } else if predicted + rounded < 0 {
+                    rounded += step;
+                }
+                residual |= (rounded as u32 & 0xff) << (channel * 8);
+            }
+            pixels[index] = residual;
+            reconstructed[index] = add_pixels(prediction, residual);
+        }
+    }
+    modes
+}
+
+// The top row always predicts from the left and the left column from above;
+// the top-right neighbour of the last column is the first pixel of its own
+// row.
+fn prediction(pixels: &[u32], width: usize, x: usize, y: usize, mode: u32) -> u32 {
+    let index = y * width + x;
+    if x == 0 && y == 0 {
+        return 0xff000000;
+    } else if y == 0 {
+        return pixels[index - 1];
+    } else if x == 0 {
+        return pixels[index - width];
+    }
+
+    let left = pixels[index - 1];
+    let top = pixels[index - width];
+    let top_left = pixels[index - width - 1];
+    let top_right = pixels[index - width + 1];
+    match mode {
+        0 => 0xff000000,
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average(average(left, top_right), top),
+        6 => average(left, top_left),
+        7 => average(left, top),
+        8 => average(top_left, top),
+        9 => average(top, top_right),
+        10 => average(average(left, top_left), average(top, top_right)),
+        11 => select(left, top, top_left),
+        12 => map_channels(|channel| {
+            let [left, top, top_left] =
+                [left, top, top_left].map(|pixel| channel_of(pixel, channel));
+            (left + top - top_left).clamp(0, 255)
+        }),
+        _ => {
+            let average = average(left, top);
+            map_channels(|channel| {
+                let [average, top_left] =
+                    [average, top_left].map(|pixel| channel_of(pixel, channel));
+                (average + (average - top_left) / 2).clamp(0, 255)
+            })
+        }
+    }
+}
+
+fn select(left: u32, top: u32, top_left: u32) -> u32 {
+    let distance_to = |pixel: u32| -> i32 {
+        (0..4)
+            .map(|channel| (channel_of(pixel, channel) - channel_of(top_left, channel)).abs())
+            .sum()
+    };
+    // Left wins when the gradient across the top row is the smaller one.
+    if distance_to(top) < distance_to(left) {
+        left
+    } else {
+        top
+    }
+}
+
+fn average(a: u32, b: u32) -> u32 {
+    map_channels(|channel| (channel_of(a, channel) + channel_of(b, channel)) / 2)
+}
+
+fn add_pixels(a: u32, b: u32) -> u32 {
+    map_channels(|channel| (channel_of(a, channel) + channel_of(b, channel)) & 0xff)
+}
+
+fn sub_pixels(a: u32, b: u32) -> u32 {
+    map_channels(|channel| (channel_of(a, channel) - channel_of(b, channel)) & 0xff)
+}
+
+// Residuals near zero in either direction are the cheap ones.
+fn residual_cost(residual: u32) -> u32 {
+    (0..4)
+        .map(|channel| {
+            let value = channel_of(residual, channel) as u32;
+            value.min(256 - value)
+        })
+        .sum()
+}
+
+fn channel_of(pixel: u32, channel: u32) -> i32 {
+    ((pixel >> (channel * 8)) & 0xff) as i32
+}
+
+fn map_channels(mut channel_value: impl FnMut(u32) -> i32) -> u32 {
+    (0..4).fold(0, |pixel, channel| {
+        pixel | (channel_value(channel) as u32) << (channel * 8)
+    })
+}
+
+enum Symbol {
+    Literal(u32),
+    Copy { length: usize, distance_code: usize },
+}
+
+// Writes an entropy-coded image: no color cache, one set of prefix codes
+// (the main image says so with its meta prefix bit), then the pixels.
+fn write_image(writer: &mut BitWriter, pixels: &[u32], width: usize, main_image: bool) {
+    writer.write(0, 1);
+    if main_image {
+        writer.write(0, 1);
+    }
+
+    let symbols = backward_references(pixels, width);
+    let mut green = [0u32; GREEN_ALPHABET_SIZE];
+    let mut red = [0u32; 256];
+    let mut blue = [0u32; 256];
+    let mut alpha = [0u32; 256];
+    let mut distance = [0u32; DISTANCE_ALPHABET_SIZE];
+    for symbol in symbols.iter() {
+        match *symbol {
+            Symbol::Literal(pixel) => {
+                green[(pixel >> 8) as usize & 0xff] += 1;
+                red[(pixel >> 16) as usize & 0xff] += 1;
+                blue[pixel as usize & 0xff] += 1;
+                alpha[(pixel >> 24) as usize] += 1;
+            }
+            Symbol::Copy {
+                length,
+                distance_code,
+            } => {
+                green[256 + prefix_code(length).0] += 1;
+                distance[prefix_code(distance_code).0] += 1;
+            }
+        }
+    }
+
+    let green = HuffmanCode::write(writer, &green);
+    let red = HuffmanCode::write(writer, &red);
+    let blue = HuffmanCode::write(writer, &blue);
+    let alpha = HuffmanCode::write(writer, &alpha);
+    let distance = HuffmanCode::write(writer, &distance);
+
+    for symbol in symbols {
+        match symbol {
+            Symbol::Literal(pixel) => {
+                green.write_symbol(writer, (pixel >> 8) as usize & 0xff);
+                red.write_symbol(writer, (pixel >> 16) as usize & 0xff);
+                blue.write_symbol(writer, pixel as usize & 0xff);
+                alpha.write_symbol(writer, (pixel >> 24) as usize);
+            }
+            Symbol::Copy {
+                length,
+                distance_code,
+            } => {
+                let (prefix, extra_bits, extra) = prefix_code(length);
+                green.write_symbol(writer, 256 + prefix);
+                writer.write(extra, extra_bits);
+                let (prefix, extra_bits, extra) = prefix_code(distance_code);
+                distance.write_symbol(writer, prefix);
+                writer.write(extra, extra_bits);
+            }
+        }
+    }
+}
+
+// Splits a length or distance code into its prefix symbol, the number of
+// extra bits and their value.
+fn prefix_code(value: usize) -> (usize, u32, u32) {
+    let value = value as u32 - 1;
+    if value < 4 {
+        return (value as usize, 0, 0);
+    }
+    let highest_bit = 31 - value.leading_zeros();
+    let second_bit = (value >> (highest_bit - 1)) & 1;
+    let extra_bits = highest_bit - 1;
+    (
+        (2 * highest_bit + second_bit) as usize,
+        extra_bits,
+        value & ((1 << extra_bits) - 1),
+    )
+}
+
+// Greedy LZ77 over hash chains of pixel pairs. The pixel to the left and
+// the one above are always tried, since runs and repeated rows are what
+// sheets with transparent padding are made of.
+fn backward_references(pixels: &[u32], width: usize) -> Vec<Symbol> {
+    let mut distance_codes = std::collections::HashMap::new();
+    for (code, (x, y)) in DISTANCE_MAP.iter().enumerate().rev() {
+        let distance = (*x as isize + *y as isize * width as isize).max(1) as usize;
+        distance_codes.insert(distance, code + 1);
+    }
+
+    let mut heads = vec![usize::MAX; 1 << HASH_BITS];
+    let mut chain = vec![usize::MAX; pixels.len()];
+    let insert = |index: usize, heads: &mut [usize], chain: &mut [usize]| {
+        if index + 1 < pixels.len() {
+            let hash = pair_hash(pixels, index);
+            chain[index] = heads[hash];
+            heads[hash] = index;
+        }
+    };
+
+    let mut symbols = Vec::new();
+    let mut index = 0;
+    while index < pixels.len() {
+        let max_length = (pixels.len() - index).min(MAX_COPY_LENGTH);
+        let match_length = |distance: usize| {
+            (0..max_length)
+                .take_while(|offset| pixels[index + offset] == pixels[index + offset - distance])
+                .count()
+        };
+
+        let mut best = (0, 0);
+        let mut candidates = vec![1, width];
+        if index + 1 < pixels.len() {
+            let mut candidate = heads[pair_hash(pixels, index)];
+            while candidate != usize::MAX && candidates.len() < MAX_CHAIN_LENGTH + 2 {
+                candidates.push(index - candidate);
+                candidate = chain[candidate];
+            }
+        }
+        for distance in candidates {
+            if distance == 0 || distance > index || distance > MAX_COPY_DISTANCE {
+                continue;
+            }
+            let length = match_length(distance);
+            if length > best.0 {
+                best = (length, distance);
+            }
+        }
+
+        let (length, distance) = best;
+        if length >= MIN_COPY_LENGTH {
+            let distance_code = distance_codes.get(&distance).copied();
+            symbols.push(Symbol::Copy {
+                length,
+                distance_code: distance_code.unwrap_or(distance + 120),
+            });
+            for skipped in index..index + length {
+                insert(skipped, &mut heads, &mut chain);
+            }
+            index += length;
+        } else {
+            symbols.push(Symbol::Literal(pixels[index]));
+            insert(index, &mut heads, &mut chain);
+            index += 1;
+        }
+    }
+    symbols
+}
+
+fn pair_hash(pixels: &[u32], index: usize) -> usize {
+    let pair = (pixels[index] as u64) << 32 | pixels[index + 1] as u64;
+    (pair.wrapping_mul(0x9e3779b97f4a7c15) >> (64 - HASH_BITS)) as usize
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    used_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.used_bits;
+        self.used_bits += bits;
+        while self.used_bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.used_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used_bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+struct HuffmanCode {
+    lengths: Vec<u8>,
+    codes: Vec<u32>,
+    single_symbol: bool,
+}
+
+impl HuffmanCode {
+    fn from_histogram(histogram: &[u32], max_length: u8) -> HuffmanCode {
+        let lengths = limited_code_lengths(histogram, max_length);
+        let mut length_count = [0u32; 16];
+        for &length in lengths.iter() {
+            length_count[length as usize] += 1;
+        }
+        length_count[0] = 0;
+
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for length in 1..16 {
+            code = (code + length_count[length - 1]) << 1;
+            next_code[length] = code;
+        }
+
+        // Codes are stored bit-reversed because the bitstream is read LSB first.
+        let mut codes = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length > 0 {
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                codes[symbol] = code.reverse_bits() >> (32 - length as u32);
+            }
+        }
+
+        // A code with a single symbol takes no bits per symbol.
+        let single_symbol = lengths.iter().filter(|length| **length > 0).count() == 1;
+        HuffmanCode {
+            lengths,
+            codes,
+            single_symbol,
+        }
+    }
+
+    fn write(writer: &mut BitWriter, histogram: &[u32]) -> HuffmanCode {
+        let used_symbols: Vec<usize> = (0..histogram.len())
+            .filter(|symbol| histogram[*symbol] > 0)
+            .collect();
+
+        // An unused alphabet, like the distances of an image without
+        // backward references, still needs a code.
+        if used_symbols.len() <= 1 && used_symbols.first().is_none_or(|symbol| *symbol < 256) {
+            let symbol = used_symbols.first().copied().unwrap_or(0);
+            writer.write(1, 1);
+            writer.write(0, 1);
+            if symbol < 2 {
+                writer.write(0, 1);
+                writer.write(symbol as u32, 1);
+            } else {
+                writer.write(1, 1);
+                writer.write(symbol as u32, 8);
+            }
+            return HuffmanCode {
+                lengths: vec![0; histogram.len()],
+                codes: vec![0; histogram.len()],
+                single_symbol: true,
+            };
+        }
+
+        let code = HuffmanCode::from_histogram(histogram, MAX_CODE_LENGTH);
+
+        let mut length_histogram = [0u32; 19];
+        for &length in code.lengths.iter() {
+            length_histogram[length as usize] += 1;
+        }
+        let length_code =
+            HuffmanCode::from_histogram(&length_histogram, MAX_CODE_LENGTH_CODE_LENGTH);
+
+        writer.write(0, 1);
+        writer.write(19 - 4, 4);
+        for symbol in CODE_LENGTH_CODE_ORDER {
+            writer.write(length_code.lengths[symbol] as u32, 3);
+        }
+        writer.write(0, 1);
+        for &length in code.lengths.iter() {
+            length_code.write_symbol(writer, length as usize);
+        }
+
+        code
+    }
+
+    fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        if self.single_symbol {
+            return;
+        }
+        writer.write(self.codes[symbol], self.lengths[symbol] as u32);
+    }
+}
+
+fn limited_code_lengths(histogram: &[u32], max_length: u8) -> Vec<u8> {
+    let mut min_count = 1;
+    loop {
+        let lengths = code_lengths(histogram, min_count);
+        if lengths.iter().all(|length| *length <= max_length) {
+            return lengths;
+        }
+        min_count *= 2;
+    }
+}
+
+fn code_lengths(histogram: &[u32], min_count: u32) -> Vec<u8> {
+    let mut lengths = vec![0u8; histogram.len()];
+    let mut nodes: Vec<(u64, Vec<usize>)> = histogram
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(symbol, count)| ((*count).max(min_count) as u64, vec![symbol]))
+        .collect();
+
+    if nodes.len() == 1 {
+        lengths[nodes[0].1[0]] = 1;
+        return lengths;
+    }
+
+    while nodes.len() > 1 {
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.0));
+        let first = nodes.pop().unwrap();
+        let second = nodes.pop().unwrap();
+        for &symbol in first.1.iter().chain(second.1.iter()) {
+            lengths[symbol] += 1;
+        }
+        let mut symbols = first.1;
+        symbols.extend(second.1);
+        nodes.push((first.0 + second.0, symbols));
+    }
+
+    lengths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(image: &image::RgbaImage, near_lossless: Option<u8>) -> Vec<u8> {
+        encode(image, near_lossless)
+            .ok()
+            .expect("the image encodes")
+    }
+
+    fn decode(webp: &[u8]) -> image::RgbaImage {
+        image::load_from_memory_with_format(webp, image::ImageFormat::WebP)
+            .unwrap()
+            .to_rgba8()
+    }
+
+    // A deterministic mix of noise, gradients, flat runs and transparency.
+    fn sheet(width: u32, height: u32) -> image::RgbaImage {
+        let mut seed = 0x2545f491u32;
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            match (x / 8 + y / 8) % 4 {
+                0 => image::Rgba(seed.to_le_bytes()),
+                1 => image::Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255]),
+                2 => image::Rgba([0, 0, 0, 0]),
+                _ => image::Rgba([200, 40, (x ^ y) as u8, (x * y) as u8]),
+            }
+        })
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        for (width, height) in [(1, 1), (1, 40), (40, 1), (17, 5), (64, 64), (130, 77)] {
+            let image = sheet(width, height);
+            let webp = encoded(&image, None);
+            assert_eq!(decode(&webp), image, "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn backward_references_shrink_repetitive_sheets() {
+        let image = image::RgbaImage::from_fn(256, 256, |x, y| {
+            image::Rgba([(x % 16 * 16) as u8, (y % 16 * 16) as u8, 0, 255])
+        });
+        let webp = encoded(&image, None);
+        assert!(webp.len() < 2048, "{} bytes", webp.len());
+        assert_eq!(decode(&webp), image);
+    }
+
+    #[test]
+    fn near_lossless_keeps_alpha_and_close_colors() {
+        let image = sheet(64, 64);
+        let decoded = decode(&encoded(&image, Some(50)));
+        for (original, decoded) in image.pixels().zip(decoded.pixels()) {
+            assert_eq!(original[3], decoded[3]);
+            for channel in 0..3 {
+                assert!(original[channel].abs_diff(decoded[channel]) <= 4);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_image_is_rejected() {
+        assert!(encode(&image::RgbaImage::new(0, 4), None).is_err());
+    }
+}
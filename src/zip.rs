@@ -0,0 +1,316 @@
+use crate::{get_image_format, ignore, input_order, sniff_bytes, ImageData, SpritesheetErr};
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+// Reads the image entries of a ZIP archive into memory. Only stored and
+// deflated entries are supported, which covers archives made by common tools.
+// Like a folder, the entries come in natural order (or by --sort-by-group) and
+// a `.packerignore` at the root of the archive leaves entries out by file name.
+pub fn read_images(
+    path: &std::path::Path,
+    sort_group: Option<usize>,
+) -> Result<Vec<ImageData>, SpritesheetErr> {
+    let error =
+        |message: &str| SpritesheetErr::ImportError(format!("{}: {}", path.display(), message));
+    let archive = std::fs::read(path).map_err(|_| error("can't read archive"))?;
+
+    let end = (0..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| read_u32(&archive, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| error("not a zip archive"))?;
+    let entries_count = read_u16(&archive, end + 10).ok_or_else(|| error("corrupt archive"))?;
+    let mut offset = read_u32(&archive, end + 16).ok_or_else(|| error("corrupt archive"))? as usize;
+
+    let mut images = Vec::new();
+    let mut ignore_patterns = Vec::new();
+    for _ in 0..entries_count {
+        if read_u32(&archive, offset) != Some(CENTRAL_DIRECTORY_HEADER) {
+            return Err(error("corrupt central directory"));
+        }
+        let header = |position: usize| read_u16(&archive, offset + position);
+        let (
+            Some(flags),
+            Some(method),
+            Some(name_length),
+            Some(extra_length),
+            Some(comment_length),
+        ) = (header(8), header(10), header(28), header(30), header(32))
+        else {
+            return Err(error("corrupt central directory"));
+        };
+        let compressed_size =
+            read_u32(&archive, offset + 20).ok_or_else(|| error("corrupt archive"))?;
+        let local_offset =
+            read_u32(&archive, offset + 42).ok_or_else(|| error("corrupt archive"))?;
+        let name_start = offset + 46;
+        let name = archive
+            .get(name_start..name_start + name_length as usize)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok_or_else(|| error("corrupt archive"))?;
+        offset =
+            name_start + name_length as usize + extra_length as usize + comment_length as usize;
+
+        if name == ".packerignore" && flags & 1 == 0 {
+            let data = read_entry(
+                &archive,
+                local_offset as usize,
+                compressed_size as usize,
+                method,
+            );
+            let data = data.ok_or_else(|| error("can't extract .packerignore"))?;
+            ignore_patterns = ignore::parse_patterns(&String::from_utf8_lossy(&data));
+            continue;
+        }
+
+        let format = std::path::Path::new(&name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(get_image_format);
+        // Entries without an image extension are only kept if their data
+        // turns out to be an image, so they can't fail the whole archive.
+        if flags & 1 != 0 {
+            if format.is_none() {
+                continue;
+            }
+            return Err(error(&format!("{} is encrypted", name)));
+        }
+
+        let data = read_entry(
+            &archive,
+            local_offset as usize,
+            compressed_size as usize,
+            method,
+        );
+        let Some(data) = data else {
+            if format.is_none() {
+                continue;
+            }
+            return Err(error(&format!("can't extract {}", name)));
+        };
+        let Some(format) = format.or_else(|| sniff_bytes(&data)) else {
+            continue;
+        };
+        images.push(ImageData {
+            path: path.join(&name),
+            name,
+            format,
+            duration: None,
+            bytes: Some(data),
+            placeholder: false,
+            page_break: false,
+            layers: Vec::new(),
+        });
+    }
+
+    images.retain(|image| {
+        let file_name = image.name.rsplit('/').next().unwrap_or_default();
+        !ignore::is_ignored(&ignore_patterns, file_name)
+    });
+    images.sort_by(|a, b| input_order(&a.name, &b.name, sort_group));
+
+    if images.is_empty() {
+        return Err(SpritesheetErr::NoImagesFound);
+    }
+    Ok(images)
+}
+
+fn read_entry(
+    archive: &[u8],
+    offset: usize,
+    compressed_size: usize,
+    method: u16,
+) -> Option<Vec<u8>> {
+    if read_u32(archive, offset)? != LOCAL_FILE_HEADER {
+        return None;
+    }
+    let data_start = offset
+        + 30
+        + read_u16(archive, offset + 26)? as usize
+        + read_u16(archive, offset + 28)? as usize;
+    let data = archive.get(data_start..data_start + compressed_size)?;
+    match method {
+        0 => Some(data.to_vec()),
+        8 => miniz_oxide::inflate::decompress_to_vec(data).ok(),
+        _ => None,
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A minimal archive of (name, method, data) entries; CRCs are left at 0
+    // since the reader doesn't check them.
+    fn archive(entries: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for (name, method, data) in entries {
+            let compressed = match method {
+                8 => miniz_oxide::deflate::compress_to_vec(data, 6),
+                _ => data.to_vec(),
+            };
+            let local_offset = archive.len() as u32;
+            for (value, size) in [
+                (LOCAL_FILE_HEADER, 4),
+                (20, 2),
+                (0, 2),
+                (*method as u32, 2),
+                (0, 4),
+                (0, 4),
+                (compressed.len() as u32, 4),
+                (data.len() as u32, 4),
+                (name.len() as u32, 2),
+                (0, 2),
+            ] {
+                archive.extend_from_slice(&value.to_le_bytes()[..size]);
+            }
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(&compressed);
+
+            for (value, size) in [
+                (CENTRAL_DIRECTORY_HEADER, 4),
+                (20, 2),
+                (20, 2),
+                (0, 2),
+                (*method as u32, 2),
+                (0, 4),
+                (0, 4),
+                (compressed.len() as u32, 4),
+                (data.len() as u32, 4),
+                (name.len() as u32, 2),
+                (0, 2),
+                (0, 2),
+                (0, 4),
+                (0, 4),
+                (local_offset, 4),
+            ] {
+                directory.extend_from_slice(&value.to_le_bytes()[..size]);
+            }
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        for (value, size) in [
+            (END_OF_CENTRAL_DIRECTORY, 4),
+            (0, 4),
+            (entries.len() as u32, 2),
+            (entries.len() as u32, 2),
+            (directory.len() as u32, 4),
+            (directory_offset, 4),
+            (0, 2),
+        ] {
+            archive.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+        archive
+    }
+
+    fn png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbaImage::new(2, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    fn read(
+        name: &str,
+        archive: &[u8],
+        sort_group: Option<usize>,
+    ) -> Result<Vec<ImageData>, SpritesheetErr> {
+        let path = std::env::temp_dir().join(format!(
+            "image_util_zip_{}_{}.zip",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, archive).unwrap();
+        let images = read_images(&path, sort_group);
+        let _ = std::fs::remove_file(&path);
+        images
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let png = png();
+        let archive = archive(&[
+            ("a.png", 0, &png),
+            ("readme.txt", 8, b"not an image"),
+            ("b.png", 8, &png),
+            ("unnamed", 8, &png),
+        ]);
+        let images = read("entries", &archive, None).ok().unwrap();
+        let names: Vec<&str> = images.iter().map(|image| image.name.as_str()).collect();
+        assert_eq!(names, ["a.png", "b.png", "unnamed"]);
+        for image in images {
+            assert_eq!(image.bytes.as_deref(), Some(png.as_slice()));
+            assert_eq!(image.format, image::ImageFormat::Png);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_archives() {
+        let png = png();
+        let valid = archive(&[("a.png", 8, &png)]);
+        assert!(read("empty", &[], None).is_err());
+        assert!(read("garbage", b"PK not really a zip archive at all", None).is_err());
+        assert!(read("no_images", &archive(&[("a.txt", 0, b"text")]), None).is_err());
+        // Cutting the archive anywhere loses the directory or an entry.
+        for length in [10, valid.len() / 2, valid.len() - 1] {
+            assert!(
+                read("truncated", &valid[..length], None).is_err(),
+                "{}",
+                length
+            );
+        }
+        // A directory offset past the end of the file.
+        let mut corrupt = valid.clone();
+        let end = corrupt.len() - 22;
+        corrupt[end + 16..end + 20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read("offset", &corrupt, None).is_err());
+        // An entry whose data isn't valid deflate.
+        let mut corrupt = valid;
+        corrupt[30 + 5] ^= 0xff;
+        corrupt[30 + 6] ^= 0xff;
+        assert!(read("deflate", &corrupt, None).is_err());
+    }
+
+    #[test]
+    fn orders_and_filters_entries_like_a_folder() {
+        let png = png();
+        let archive = archive(&[
+            (".packerignore", 0, b"# drafts\n*_old.png\n"),
+            ("walk_10_2.png", 8, &png),
+            ("walk_2_3.png", 8, &png),
+            ("walk_2_old.png", 8, &png),
+            ("run/walk_1_1.png", 8, &png),
+        ]);
+        let names = |sort_group| -> Vec<String> {
+            let images = read("order", &archive, sort_group).ok().unwrap();
+            images.into_iter().map(|image| image.name).collect()
+        };
+        assert_eq!(
+            names(None),
+            ["run/walk_1_1.png", "walk_2_3.png", "walk_10_2.png"]
+        );
+        assert_eq!(
+            names(Some(2)),
+            ["run/walk_1_1.png", "walk_10_2.png", "walk_2_3.png"]
+        );
+    }
+}